@@ -0,0 +1,43 @@
+#![allow(unused)]
+
+use macroquad::prelude::*;
+
+
+/// Number of network outputs used by `Actions::from_outputs`.
+pub const ACTIONS_NUM: usize = 5;
+
+/// Physical actions of a molecule decoded from the outputs of its network.
+#[derive(Clone, Copy)]
+pub struct Actions {
+    /// Forward thrust along the heading, in [0, 1].
+    pub thrust: f32,
+    /// Turning effort, in [-1, 1].
+    pub torque: f32,
+    /// Braking effort, in [0, 1].
+    pub brake: f32,
+    pub emit: bool,
+    pub bond: bool,
+}
+
+impl Actions {
+    pub fn new() -> Self {
+        Self { thrust: 0.0, torque: 0.0, brake: 0.0, emit: false, bond: true }
+    }
+
+    /// Outputs are expected in [-1, 1] (tanh); missing outputs count as zero.
+    pub fn from_outputs(outputs: &[f32]) -> Self {
+        let output = |i: usize| outputs.get(i).copied().unwrap_or(0.0).clamp(-1.0, 1.0);
+        Self {
+            thrust: (output(0) + 1.0) / 2.0,
+            torque: output(1),
+            brake: output(2).max(0.0),
+            emit: output(3) > 0.0,
+            bond: output(4) > 0.0,
+        }
+    }
+
+    /// Relative effort of the actions, used to charge their energy cost.
+    pub fn effort(&self) -> f32 {
+        return self.thrust + 0.5*self.torque.abs() + 0.1*self.brake + if self.emit { 0.5 } else { 0.0 };
+    }
+}
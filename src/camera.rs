@@ -17,11 +17,9 @@ pub fn control_camera(camera: &mut Camera2D, screen_ratio: f32) {
         let ratio = screen_ratio;
         camera.zoom += Vec2::new(0.0001, 0.0001*ratio);
     }
-    if is_key_pressed(KeyCode::KpSubtract) {
-        if camera.zoom.x > 0.0001 {
-            let ratio = screen_ratio;
-            camera.zoom -= Vec2::new(0.0001, 0.0001*ratio);
-        }
+    if is_key_pressed(KeyCode::KpSubtract) && camera.zoom.x > 0.0001 {
+        let ratio = screen_ratio;
+        camera.zoom -= Vec2::new(0.0001, 0.0001*ratio);
     }
     if is_key_pressed(KeyCode::Left) {
         //println!("target <x: {} | y: {}>", camera.target.x, camera.target.y);
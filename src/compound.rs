@@ -0,0 +1,248 @@
+#![allow(unused)]
+
+use std::collections::{HashMap, HashSet, BTreeMap};
+use macroquad::prelude::*;
+use crate::particle::*;
+use crate::species::*;
+use crate::world::*;
+
+
+/// Undirected graph of chemical bonds between molecules, keyed by `MoleculesBox` ids.
+pub struct BondGraph {
+    edges: HashMap<u64, HashSet<u64>>,
+    formed: HashMap<(u64, u64), f64>,
+}
+
+fn bond_key(id1: u64, id2: u64) -> (u64, u64) {
+    return (id1.min(id2), id1.max(id2));
+}
+
+impl BondGraph {
+    pub fn new() -> Self {
+        Self { edges: HashMap::new(), formed: HashMap::new() }
+    }
+
+    pub fn add_bond(&mut self, id1: u64, id2: u64, time: f64) {
+        if id1 == id2 {
+            return;
+        }
+        self.formed.insert(bond_key(id1, id2), time);
+        self.edges.entry(id1).or_default().insert(id2);
+        self.edges.entry(id2).or_default().insert(id1);
+    }
+
+    pub fn remove_bond(&mut self, id1: u64, id2: u64) {
+        self.formed.remove(&bond_key(id1, id2));
+        for (a, b) in [(id1, id2), (id2, id1)] {
+            if let Some(neighbours) = self.edges.get_mut(&a) {
+                neighbours.remove(&b);
+                if neighbours.is_empty() {
+                    self.edges.remove(&a);
+                }
+            }
+        }
+    }
+
+    pub fn remove_molecule(&mut self, id: u64) {
+        if let Some(neighbours) = self.edges.remove(&id) {
+            for other in neighbours {
+                self.remove_bond(id, other);
+            }
+        }
+    }
+
+    pub fn are_bonded(&self, id1: u64, id2: u64) -> bool {
+        return self.edges.get(&id1).is_some_and(|n| n.contains(&id2));
+    }
+
+    /// Number of bonds of a molecule.
+    pub fn degree(&self, id: u64) -> usize {
+        return self.edges.get(&id).map_or(0, |n| n.len());
+    }
+
+    pub fn neighbours(&self, id: u64) -> impl Iterator<Item = &u64> {
+        return self.edges.get(&id).into_iter().flatten();
+    }
+
+    /// Simulation time at which the bond was formed.
+    pub fn formed_at(&self, id1: u64, id2: u64) -> Option<f64> {
+        return self.formed.get(&bond_key(id1, id2)).copied();
+    }
+
+    pub fn bonds_num(&self) -> usize {
+        return self.edges.values().map(|n| n.len()).sum::<usize>() / 2;
+    }
+
+    pub fn clear(&mut self) {
+        self.edges.clear();
+        self.formed.clear();
+    }
+
+    /// Connected components with at least two members.
+    pub fn components(&self) -> Vec<Vec<u64>> {
+        let mut visited: HashSet<u64> = HashSet::new();
+        let mut components: Vec<Vec<u64>> = vec![];
+        for start in self.edges.keys() {
+            if visited.contains(start) {
+                continue;
+            }
+            let mut component: Vec<u64> = vec![];
+            let mut stack: Vec<u64> = vec![*start];
+            visited.insert(*start);
+            while let Some(id) = stack.pop() {
+                component.push(id);
+                for other in self.neighbours(id) {
+                    if visited.insert(*other) {
+                        stack.push(*other);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        return components;
+    }
+}
+
+/// A group of molecules connected by bonds.
+pub struct Compound {
+    pub members: Vec<u64>,
+    /// Species of the members, in the same order as `members`.
+    pub species: Vec<usize>,
+    pub bonds: Vec<(u64, u64)>,
+    pub formula: String,
+    pub mass: f32,
+    pub centroid: Vec2,
+    pub velocity: Vec2,
+    /// Time since the oldest bond still holding the compound together was formed.
+    pub age: f64,
+}
+
+impl Compound {
+    pub fn new(members: Vec<u64>, graph: &BondGraph, molecules: &MoleculesBox, registry: &SpeciesRegistry, world: &World, sim_time: f64) -> Self {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        let mut species: Vec<usize> = vec![];
+        let mut bonds: Vec<(u64, u64)> = vec![];
+        let mut formed_at = f64::MAX;
+        let mut mass: f32 = 0.0;
+        let mut centroid = Vec2::ZERO;
+        let mut momentum = Vec2::ZERO;
+        for id in members.iter() {
+            for other in graph.neighbours(*id) {
+                if *id < *other {
+                    bonds.push((*id, *other));
+                    formed_at = formed_at.min(graph.formed_at(*id, *other).unwrap_or(0.0));
+                }
+            }
+            let Some(molecule) = molecules.get(*id) else {
+                species.push(usize::MAX);
+                continue;
+            };
+            species.push(molecule.species);
+            *counts.entry(molecule.species).or_insert(0) += 1;
+            if let Some(handle) = molecule.physics_handle {
+                let data = world.get_physics_data(handle);
+                mass += data.mass;
+                centroid += data.position * data.mass;
+                momentum += data.velocity * data.mass;
+            }
+        }
+        if mass > 0.0 {
+            centroid /= mass;
+            momentum /= mass;
+        }
+        Self {
+            members: members,
+            species: species,
+            bonds: bonds,
+            formula: formula(&counts, registry),
+            mass: mass,
+            centroid: centroid,
+            velocity: momentum,
+            age: if formed_at == f64::MAX { 0.0 } else { sim_time - formed_at },
+        }
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        return self.members.contains(&id);
+    }
+
+    pub fn are_bonded(&self, id1: u64, id2: u64) -> bool {
+        return self.bonds.contains(&bond_key(id1, id2));
+    }
+
+    pub fn size(&self) -> usize {
+        return self.members.len();
+    }
+}
+
+/// Builds a formula in Hill order: carbon first, hydrogen second, the rest alphabetically.
+pub fn formula(counts: &HashMap<usize, usize>, registry: &SpeciesRegistry) -> String {
+    let mut symbols: BTreeMap<String, usize> = BTreeMap::new();
+    for (species_id, count) in counts.iter() {
+        if let Some(species) = registry.get(*species_id) {
+            *symbols.entry(species.symbol.clone()).or_insert(0) += count;
+        }
+    }
+    let mut ordered: Vec<(String, usize)> = vec![];
+    if symbols.contains_key("C") {
+        for symbol in ["C", "H"] {
+            if let Some(count) = symbols.remove(symbol) {
+                ordered.push((symbol.to_string(), count));
+            }
+        }
+    }
+    ordered.extend(symbols);
+    let mut text = String::new();
+    for (symbol, count) in ordered {
+        text.push_str(&symbol);
+        if count > 1 {
+            text.push_str(&count.to_string());
+        }
+    }
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> SpeciesRegistry {
+        return SpeciesRegistry::parse("Hydrogen, H, 2.0, 1.0, #FFFFFF, 24, 0.9, 0.1, 1
+Carbon, C, 5.0, 1.0, #606060, 48, 0.9, 0.1, 4
+Nitrogen, N, 4.5, 1.0, #3050F8, 44, 0.9, 0.1, 3
+Oxygen, O, 4.0, 1.2, #FF0D0D, 40, 0.9, 0.1, 2
+Sodium, Na, 6.0, 0.8, #AB5CF2, 32, 0.9, 0.1, 1
+Chlorine, Cl, 5.5, 1.1, #1FF01F, 36, 0.9, 0.1, 1").unwrap();
+    }
+
+    fn counts(registry: &SpeciesRegistry, symbols: &[(&str, usize)]) -> HashMap<usize, usize> {
+        return symbols.iter().map(|(s, n)| (registry.find(s).unwrap(), *n)).collect();
+    }
+
+    #[test]
+    fn formula_uses_hill_order() {
+        let registry = registry();
+        assert_eq!(formula(&counts(&registry, &[("O", 1), ("N", 1), ("H", 4), ("C", 1)]), &registry), "CH4NO");
+        assert_eq!(formula(&counts(&registry, &[("O", 1), ("H", 2)]), &registry), "H2O");
+        assert_eq!(formula(&counts(&registry, &[("Na", 1), ("Cl", 1)]), &registry), "ClNa");
+    }
+
+    #[test]
+    fn components_follow_bonds() {
+        let mut graph = BondGraph::new();
+        graph.add_bond(1, 2, 0.0);
+        graph.add_bond(2, 3, 1.0);
+        graph.add_bond(4, 5, 2.0);
+        let mut sizes: Vec<usize> = graph.components().iter().map(|c| c.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 3]);
+        assert_eq!(graph.bonds_num(), 3);
+        assert_eq!(graph.degree(2), 2);
+        graph.remove_molecule(2);
+        let mut remaining = graph.components();
+        remaining[0].sort();
+        assert_eq!(remaining, vec![vec![4, 5]]);
+        assert!(!graph.are_bonded(1, 2));
+        assert_eq!(graph.bonds_num(), 1);
+    }
+}
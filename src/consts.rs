@@ -6,8 +6,48 @@ pub const WORLD_W: f32=800.0;
 pub const WORLD_H: f32=800.0;
 pub const MOLECULE_NUM: usize=10;
 pub const MOLECULE_NUM_MIN: usize=10;
-pub const MOLECULE_SIZE_MIN: i32=2;
-pub const MOLECULE_SIZE_MAX: i32=6;
 pub const MOLECULE_SPEED: f32=25.0;
 pub const FIX_DT: f32 = 1.0/30.0;
-pub const GRAV: f32=200000.0;
\ No newline at end of file
+pub const MAX_SUBSTEPS: usize=10;
+pub const GRAV: f32=200000.0;
+pub const BOND_STIFFNESS: f32=40.0;
+pub const BOND_DAMPING: f32=4.0;
+pub const BOND_BREAK_RATIO: f32=1.5;
+pub const MAX_CHARGE: f32=2.0;
+pub const COULOMB_K: f32=20000.0;
+pub const CONTACT_FORCE_THRESHOLD: f32=10.0;
+pub const TEMPERATURE: f32=10000.0;
+pub const ROCKS_NUM: usize=3;
+pub const SPECIES_PATH: &str="assets/data/species.csv";
+pub const REACTIONS_PATH: &str="assets/data/reactions.csv";
+pub const POPULATION_INTERVAL: f64=0.5;
+pub const POPULATION_SAMPLES: usize=600;
+pub const ENERGY_TOLERANCE: f32=0.05;
+pub const FIELD_CELL_SIZE: f32=25.0;
+pub const HEAT_RATIO: f32=0.01;
+pub const ABSORB_ENG_RATIO: f32=1.0;
+pub const SOURCES_NUM: usize=8;
+pub const SOURCES_NUM_MIN: usize=4;
+pub const SOURCE_DRAIN_RATE: f32=50.0;
+pub const MOLECULE_ENG: f32=20.0;
+pub const BASE_METABOLISM: f32=0.1;
+pub const MOVE_COST: f32=0.01;
+pub const REACTION_ENG_RATIO: f32=0.05;
+pub const DEATH_LOG_SIZE: usize=100;
+pub const FISSION_ENG_RATIO: f32=0.95;
+pub const MUTATION_RATE: f32=0.2;
+pub const MUTATION_MAGNITUDE: f32=0.1;
+pub const SENSOR_RAYS: usize=7;
+pub const SENSOR_FAN: f32=2.0;
+pub const BRAIN_HIDDEN: usize=8;
+pub const MOLECULE_TURN: f32=2.0;
+pub const THRUST_RATE: f32=2.0;
+pub const TURN_RATE: f32=5.0;
+pub const BRAKE_RATE: f32=3.0;
+pub const ACTION_COST: f32=1.0;
+pub const EMIT_RATE: f32=5.0;
+pub const GENERATION_SIZE: usize=64;
+pub const GENERATION_TIME: f32=60.0;
+pub const TOURNAMENT_SIZE: usize=4;
+pub const ELITISM: usize=2;
+pub const FITNESS_ENG_WEIGHT: f32=0.1;
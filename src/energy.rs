@@ -0,0 +1,67 @@
+#![allow(unused)]
+
+
+/// Running account of the energy in the simulation. Chemical energy is the energy
+/// stored in molecules: exothermic events move it into kinetic energy and endothermic
+/// events take kinetic energy back. External work (force fields, actuators, contact
+/// losses, thermostat, heat and molecules added or removed with their energy) is
+/// booked separately, so kinetic + potential + chemical - external should stay
+/// constant between steps.
+pub struct EnergyLedger {
+    pub kinetic: f32,
+    pub potential: f32,
+    pub chemical: f32,
+    pub external: f32,
+    /// Sum of all unaccounted changes since the start.
+    pub drift: f32,
+    /// Unaccounted change of the last step relative to the total mechanical energy.
+    pub step_error: f32,
+    pub warning: bool,
+    step_start: f32,
+    step_external: f32,
+}
+
+impl EnergyLedger {
+    pub fn new() -> Self {
+        Self {
+            kinetic: 0.0,
+            potential: 0.0,
+            chemical: 0.0,
+            external: 0.0,
+            drift: 0.0,
+            step_error: 0.0,
+            warning: false,
+            step_start: 0.0,
+            step_external: 0.0,
+        }
+    }
+
+    pub fn begin_step(&mut self, kinetic: f32, potential: f32) {
+        self.step_start = kinetic + potential + self.chemical;
+        self.step_external = 0.0;
+    }
+
+    /// Work done on the system from outside of the physics (positive adds energy).
+    pub fn add_external(&mut self, energy: f32) {
+        self.external += energy;
+        self.step_external += energy;
+    }
+
+    /// Chemical energy turned into kinetic energy (negative for endothermic events).
+    pub fn release_chemical(&mut self, energy: f32) {
+        self.chemical -= energy;
+    }
+
+    pub fn end_step(&mut self, kinetic: f32, potential: f32, tolerance: f32) {
+        self.kinetic = kinetic;
+        self.potential = potential;
+        let unaccounted = self.total() - self.step_start - self.step_external;
+        self.drift += unaccounted;
+        self.step_error = unaccounted / (kinetic + potential).abs().max(1.0);
+        self.warning = self.step_error.abs() > tolerance;
+    }
+
+    pub fn total(&self) -> f32 {
+        return self.kinetic + self.potential + self.chemical;
+    }
+}
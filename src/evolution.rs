@@ -0,0 +1,202 @@
+#![allow(unused)]
+
+use ::rand::{Rng, thread_rng};
+use crate::consts::*;
+use crate::genome::*;
+use crate::particle::*;
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EvolutionMode {
+    /// Open-ended evolution driven by metabolism and fission.
+    Continuous,
+    /// Epochs of fixed length after which the world is repopulated from the best genomes.
+    Generational,
+}
+
+impl EvolutionMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvolutionMode::Continuous => "Continuous",
+            EvolutionMode::Generational => "Generational",
+        }
+    }
+}
+
+pub fn evolution_mode_variants() -> [EvolutionMode; 2] {
+    return [EvolutionMode::Continuous, EvolutionMode::Generational];
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Selection {
+    Tournament { size: usize },
+    Roulette,
+}
+
+impl Selection {
+    pub fn default_tournament() -> Self {
+        return Selection::Tournament { size: TOURNAMENT_SIZE };
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Selection::Tournament { .. } => "Tournament",
+            Selection::Roulette => "Roulette",
+        }
+    }
+
+    /// Index of a parent picked from scored genomes.
+    fn pick(&self, scores: &[(Genome, f32)]) -> usize {
+        let mut rng = thread_rng();
+        match *self {
+            Selection::Tournament { size } => {
+                let mut best = rng.gen_range(0..scores.len());
+                for _ in 1..size.max(1) {
+                    let other = rng.gen_range(0..scores.len());
+                    if scores[other].1 > scores[best].1 {
+                        best = other;
+                    }
+                }
+                return best;
+            },
+            Selection::Roulette => {
+                // Fitness is shifted so that the worst genome still has a small chance.
+                let min = scores.iter().map(|(_, f)| *f).fold(f32::MAX, f32::min);
+                let weights: Vec<f32> = scores.iter().map(|(_, f)| f - min + 0.01).collect();
+                let mut ball = rng.gen_range(0.0..weights.iter().sum::<f32>());
+                for (i, weight) in weights.iter().enumerate() {
+                    if ball < *weight {
+                        return i;
+                    }
+                    ball -= weight;
+                }
+                return scores.len()-1;
+            },
+        }
+    }
+}
+
+pub fn selection_variants() -> [Selection; 2] {
+    return [Selection::default_tournament(), Selection::Roulette];
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Crossover {
+    None,
+    Uniform,
+    OnePoint,
+}
+
+impl Crossover {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Crossover::None => "None",
+            Crossover::Uniform => "Uniform",
+            Crossover::OnePoint => "One Point",
+        }
+    }
+
+    fn apply(&self, parent1: &Genome, parent2: &Genome) -> Genome {
+        match self {
+            Crossover::None => parent1.clone(),
+            Crossover::Uniform => parent1.crossover_uniform(parent2),
+            Crossover::OnePoint => parent1.crossover_one_point(parent2),
+        }
+    }
+}
+
+pub fn crossover_variants() -> [Crossover; 3] {
+    return [Crossover::None, Crossover::Uniform, Crossover::OnePoint];
+}
+
+/// Settings of the generational mode.
+#[derive(Clone)]
+pub struct EvolutionConfig {
+    pub mode: EvolutionMode,
+    pub generation_size: usize,
+    pub generation_time: f32,
+    pub selection: Selection,
+    pub crossover: Crossover,
+    pub elitism: usize,
+}
+
+impl EvolutionConfig {
+    pub fn new() -> Self {
+        Self {
+            mode: EvolutionMode::Continuous,
+            generation_size: GENERATION_SIZE,
+            generation_time: GENERATION_TIME,
+            selection: Selection::default_tournament(),
+            crossover: Crossover::Uniform,
+            elitism: ELITISM,
+        }
+    }
+}
+
+/// Rewards staying alive and the energy left at the end of the generation.
+pub fn fitness(molecule: &Molecule) -> f32 {
+    return molecule.lifetime + molecule.eng * FITNESS_ENG_WEIGHT;
+}
+
+#[derive(Clone, Copy)]
+pub struct GenerationStats {
+    pub generation: u32,
+    pub best: f32,
+    pub mean: f32,
+}
+
+/// Collects scored genomes during a generation and breeds the next one.
+pub struct Evolution {
+    pub generation: u32,
+    pub started: f64,
+    pub history: Vec<GenerationStats>,
+    scores: Vec<(Genome, f32)>,
+}
+
+impl Evolution {
+    pub fn new() -> Self {
+        Self {
+            generation: 0,
+            started: 0.0,
+            history: vec![],
+            scores: vec![],
+        }
+    }
+
+    pub fn record(&mut self, molecule: &Molecule) {
+        self.scores.push((molecule.genome.clone(), fitness(molecule)));
+    }
+
+    pub fn elapsed(&self, sim_time: f64) -> f64 {
+        return sim_time - self.started;
+    }
+
+    /// Ends the current generation and returns genomes of the next one. The best
+    /// `elitism` genomes pass unchanged, the rest are children of selected parents.
+    pub fn breed(&mut self, config: &EvolutionConfig, mutation_rate: f32, mutation_magnitude: f32) -> Vec<Genome> {
+        let mut scores = std::mem::take(&mut self.scores);
+        self.generation += 1;
+        if scores.is_empty() {
+            return vec![];
+        }
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mean = scores.iter().map(|(_, f)| *f).sum::<f32>() / scores.len() as f32;
+        self.history.push(GenerationStats { generation: self.generation, best: scores[0].1, mean: mean });
+        let mut genomes: Vec<Genome> = scores.iter().take(config.elitism.min(config.generation_size)).map(|(g, _)| g.clone()).collect();
+        while genomes.len() < config.generation_size {
+            let parent1 = &scores[config.selection.pick(&scores)].0;
+            let parent2 = &scores[config.selection.pick(&scores)].0;
+            let mut child = config.crossover.apply(parent1, parent2);
+            child.mutate(mutation_rate, mutation_magnitude);
+            genomes.push(child);
+        }
+        return genomes;
+    }
+
+    pub fn clear(&mut self) {
+        self.generation = 0;
+        self.started = 0.0;
+        self.history.clear();
+        self.scores.clear();
+    }
+}
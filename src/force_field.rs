@@ -0,0 +1,81 @@
+#![allow(unused)]
+
+use macroquad::prelude::*;
+use crate::consts::*;
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ForceField {
+    None,
+    Attractor { center: Vec2, strength: f32 },
+    Gravity { acceleration: Vec2 },
+    Vortex { center: Vec2, strength: f32 },
+    Shear { strength: f32 },
+}
+
+impl ForceField {
+    pub fn default_attractor() -> Self {
+        return ForceField::Attractor { center: Vec2::new(WORLD_W/2.0, WORLD_H/2.0), strength: GRAV };
+    }
+
+    pub fn default_gravity() -> Self {
+        return ForceField::Gravity { acceleration: Vec2::new(0.0, 10.0) };
+    }
+
+    pub fn default_vortex() -> Self {
+        return ForceField::Vortex { center: Vec2::new(WORLD_W/2.0, WORLD_H/2.0), strength: GRAV };
+    }
+
+    pub fn default_shear() -> Self {
+        return ForceField::Shear { strength: 10.0 };
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForceField::None => "None",
+            ForceField::Attractor { .. } => "Attractor",
+            ForceField::Gravity { .. } => "Gravity",
+            ForceField::Vortex { .. } => "Vortex",
+            ForceField::Shear { .. } => "Shear",
+        }
+    }
+
+    /// Force acting on a body of given mass at given position.
+    pub fn force_at(&self, pos: Vec2, mass: f32) -> Vec2 {
+        match *self {
+            ForceField::None => Vec2::ZERO,
+            ForceField::Attractor { center, strength } => {
+                let offset = center - pos;
+                let r = offset.length().max(1.0);
+                return offset.normalize_or_zero() * (strength / r);
+            },
+            ForceField::Gravity { acceleration } => {
+                return acceleration * mass;
+            },
+            ForceField::Vortex { center, strength } => {
+                let offset = pos - center;
+                let r = offset.length().max(1.0);
+                let tangent = Vec2::new(-offset.y, offset.x).normalize_or_zero();
+                return tangent * (strength / r);
+            },
+            ForceField::Shear { strength } => {
+                let rel_y = (pos.y - WORLD_H/2.0) / (WORLD_H/2.0);
+                return Vec2::new(strength * rel_y * mass, 0.0);
+            },
+        }
+    }
+}
+
+pub fn default_force_fields() -> Vec<ForceField> {
+    return vec![ForceField::default_attractor()];
+}
+
+pub fn force_field_variants() -> [ForceField; 5] {
+    return [
+        ForceField::None,
+        ForceField::default_attractor(),
+        ForceField::default_gravity(),
+        ForceField::default_vortex(),
+        ForceField::default_shear(),
+    ];
+}
@@ -0,0 +1,235 @@
+#![allow(unused)]
+
+use macroquad::{prelude::*, color};
+use ::rand::{Rng, thread_rng};
+use crate::consts::*;
+use crate::neuro::*;
+use crate::species::*;
+use crate::util::*;
+use crate::actuator::ACTIONS_NUM;
+use crate::particle::brain_inputs_num;
+
+
+/// Number of trait genes preceding the network parameters in the gene vector.
+const TRAITS_NUM: usize = 8;
+
+/// All heritable traits of a molecule. The species id is kept as is, while the
+/// physical parameters start from the species and evolve on their own. Charge and
+/// valence are inherited but never mutated: charge is conserved on fission and
+/// valence belongs to the chemistry of the species.
+#[derive(Clone)]
+pub struct Genome {
+    pub species: usize,
+    pub charge: f32,
+    pub valence: u32,
+    pub size: f32,
+    pub color: color::Color,
+    pub field_range: f32,
+    pub density: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub network: Network,
+}
+
+impl Genome {
+    /// Traits of the species with a random charge and a freshly initialised network.
+    pub fn random(species_id: usize, registry: &SpeciesRegistry) -> Self {
+        let species_id = registry.clamp_id(species_id);
+        let species = registry.get_clamped(species_id);
+        Self {
+            species: species_id,
+            charge: rand::gen_range(-MAX_CHARGE as i32, MAX_CHARGE as i32 + 1) as f32,
+            valence: species.valence,
+            size: species.radius,
+            color: species.color,
+            field_range: species.field_range,
+            density: species.density,
+            restitution: species.restitution,
+            friction: species.friction,
+            network: Network::new(&[brain_inputs_num(), BRAIN_HIDDEN, ACTIONS_NUM], Activation::Tanh, Activation::Tanh),
+        }
+    }
+
+    /// Species record with the physical parameters of this genome, used to build the body.
+    pub fn express_species(&self, registry: &SpeciesRegistry) -> Species {
+        let mut species = registry.get_clamped(self.species).clone();
+        species.radius = self.size;
+        species.color = self.color;
+        species.field_range = self.field_range;
+        species.density = self.density;
+        species.restitution = self.restitution;
+        species.friction = self.friction;
+        species.valence = self.valence;
+        return species;
+    }
+
+    /// Each gene changes with probability `rate`. Traits are scaled by a gaussian factor
+    /// around 1 of width `magnitude`, network parameters get gaussian noise of that width.
+    pub fn mutate(&mut self, rate: f32, magnitude: f32) {
+        let mut genes = self.get_genes();
+        let mut rng = thread_rng();
+        for (i, gene) in genes.iter_mut().enumerate() {
+            if rng.gen_range(0.0..1.0) >= rate {
+                continue;
+            }
+            if i < TRAITS_NUM {
+                *gene = mutate(*gene, magnitude);
+            } else {
+                *gene += random_gauss() * magnitude;
+            }
+        }
+        self.set_genes(&genes);
+    }
+
+    /// Each gene is taken from one of the parents at random.
+    pub fn crossover_uniform(&self, other: &Genome) -> Genome {
+        let (genes1, genes2) = (self.get_genes(), other.get_genes());
+        if genes1.len() != genes2.len() {
+            return self.clone();
+        }
+        let mut rng = thread_rng();
+        let genes: Vec<f32> = genes1.iter().zip(genes2.iter()).map(|(a, b)| if rng.gen_bool(0.5) { *a } else { *b }).collect();
+        let mut child = self.clone();
+        child.set_genes(&genes);
+        return child;
+    }
+
+    /// Genes before a random cut point come from this parent, the rest from the other.
+    pub fn crossover_one_point(&self, other: &Genome) -> Genome {
+        let (genes1, genes2) = (self.get_genes(), other.get_genes());
+        if genes1.len() != genes2.len() {
+            return self.clone();
+        }
+        let cut = thread_rng().gen_range(0..=genes1.len());
+        let genes: Vec<f32> = genes1[..cut].iter().chain(genes2[cut..].iter()).copied().collect();
+        let mut child = self.clone();
+        child.set_genes(&genes);
+        return child;
+    }
+
+    /// Flat gene vector: traits followed by the network weights and biases. The species
+    /// id and the network topology are not part of it and come from the first parent.
+    pub fn get_genes(&self) -> Vec<f32> {
+        let mut genes = vec![
+            self.size, self.color.r, self.color.g, self.color.b, self.field_range,
+            self.density, self.restitution, self.friction,
+        ];
+        genes.extend(self.network.get_params());
+        return genes;
+    }
+
+    /// Sets genes and keeps traits within their valid ranges. Non-finite genes keep
+    /// their current value.
+    pub fn set_genes(&mut self, genes: &[f32]) {
+        if genes.len() < TRAITS_NUM {
+            return;
+        }
+        let current = self.get_genes();
+        let genes: Vec<f32> = genes.iter().enumerate().map(|(i, g)| if g.is_finite() { *g } else { current.get(i).copied().unwrap_or(0.0) }).collect();
+        self.size = genes[0].clamp(1.0, 20.0);
+        self.color = Color::new(genes[1].clamp(0.05, 1.0), genes[2].clamp(0.05, 1.0), genes[3].clamp(0.05, 1.0), 1.0);
+        self.field_range = genes[4].clamp(2.0*self.size, 100.0);
+        self.density = genes[5].clamp(0.1, 10.0);
+        self.restitution = genes[6].clamp(0.0, 1.0);
+        self.friction = genes[7].clamp(0.0, 1.0);
+        _ = self.network.set_params(&genes[TRAITS_NUM..]);
+    }
+
+    /// Text form: species name with charge and valence, traits and the serialized network.
+    pub fn serialize(&self, registry: &SpeciesRegistry) -> String {
+        let species_name = registry.get(self.species).map_or("?", |s| s.name.as_str());
+        let traits: Vec<String> = self.get_genes()[..TRAITS_NUM].iter().map(|g| g.to_string()).collect();
+        return format!("genome {} {} {}\ntraits {}\n{}", species_name, self.charge, self.valence, traits.join(" "), self.network.serialize());
+    }
+
+    pub fn deserialize(text: &str, registry: &SpeciesRegistry) -> Result<Self, String> {
+        let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+        let header = lines.next().ok_or("empty genome description")?;
+        let mut header_fields = header.split_whitespace();
+        if header_fields.next() != Some("genome") {
+            return Err("missing genome header".to_string());
+        }
+        let name = header_fields.next().ok_or("missing species name")?;
+        let species = registry.find(name).ok_or(format!("unknown species '{}'", name))?;
+        let charge = header_fields.next().ok_or("missing charge")?.parse::<f32>().map_err(|_| "invalid charge".to_string())?;
+        let valence = header_fields.next().ok_or("missing valence")?.parse::<u32>().map_err(|_| "invalid valence".to_string())?;
+        let traits_line = lines.next().ok_or("missing traits")?;
+        let traits: Vec<f32> = traits_line.strip_prefix("traits ").ok_or("missing traits")?
+            .split_whitespace().map(|g| g.parse::<f32>().map_err(|_| format!("invalid gene '{}'", g))).collect::<Result<_, _>>()?;
+        if traits.len() != TRAITS_NUM {
+            return Err(format!("expected {} traits, found {}", TRAITS_NUM, traits.len()));
+        }
+        let network = Network::deserialize(&lines.collect::<Vec<&str>>().join("\n"))?;
+        if !traits.iter().chain(network.get_params().iter()).all(|g| g.is_finite()) {
+            return Err("non-finite gene".to_string());
+        }
+        let mut genome = Genome::random(species, registry);
+        genome.charge = charge.clamp(-MAX_CHARGE, MAX_CHARGE);
+        genome.valence = valence;
+        genome.network = network;
+        let mut genes = traits;
+        genes.extend(genome.network.get_params());
+        genome.set_genes(&genes);
+        return Ok(genome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> SpeciesRegistry {
+        return SpeciesRegistry::parse("Hydrogen, H, 2.0, 1.0, #FFFFFF, 24, 0.9, 0.1, 1
+Oxygen, O, 4.0, 1.2, #FF0D0D, 40, 0.9, 0.1, 2").unwrap();
+    }
+
+    #[test]
+    fn crossover_takes_genes_from_parents() {
+        let registry = registry();
+        let (parent1, parent2) = (Genome::random(0, &registry), Genome::random(1, &registry));
+        let (genes1, genes2) = (parent1.get_genes(), parent2.get_genes());
+        for child in [parent1.crossover_uniform(&parent2), parent1.crossover_one_point(&parent2)] {
+            let genes = child.get_genes();
+            assert_eq!(genes.len(), genes1.len());
+            assert!(genes.iter().enumerate().all(|(i, g)| *g == genes1[i] || *g == genes2[i]));
+            assert_eq!(child.species, parent1.species);
+        }
+    }
+
+    #[test]
+    fn mutation_with_zero_rate_keeps_genes() {
+        let registry = registry();
+        let mut genome = Genome::random(1, &registry);
+        let genes = genome.get_genes();
+        genome.mutate(0.0, 1.0);
+        assert_eq!(genome.get_genes(), genes);
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let registry = registry();
+        let mut genome = Genome::random(1, &registry);
+        genome.mutate(1.0, 0.3);
+        let restored = Genome::deserialize(&genome.serialize(&registry), &registry).unwrap();
+        assert_eq!(restored.species, genome.species);
+        assert_eq!(restored.charge, genome.charge);
+        assert_eq!(restored.valence, genome.valence);
+        assert_eq!(restored.get_genes(), genome.get_genes());
+        assert!(Genome::deserialize("genome Xenon 0 0", &registry).is_err());
+    }
+
+    #[test]
+    fn non_finite_genes_are_rejected() {
+        let registry = registry();
+        let mut genome = Genome::random(1, &registry);
+        let mut genes = genome.get_genes();
+        genes[0] = f32::NAN;
+        genes[4] = f32::INFINITY;
+        genes[TRAITS_NUM] = f32::NAN;
+        genome.set_genes(&genes);
+        assert!(genome.get_genes().iter().all(|g| g.is_finite()));
+        let mut lines: Vec<String> = genome.serialize(&registry).lines().map(|l| l.to_string()).collect();
+        lines[1] = format!("traits NaN {}", lines[1].split_whitespace().skip(2).collect::<Vec<&str>>().join(" "));
+        assert!(Genome::deserialize(&lines.join("\n"), &registry).is_err());
+    }
+}
@@ -3,7 +3,6 @@ use std::collections::HashMap;
 use parry2d::query::contact;
 use parry2d::{math::*, query::Contact}; 
 use parry2d::shape::*;
-use glam;
 use nalgebra::*;
 use macroquad::math::Vec2;
 
@@ -33,10 +32,7 @@ pub fn contact_mouse(mouse_pos: Vec2, target_pos: Vec2, target_rad: f32) -> bool
     let pos2 = make_isometry(v2.x, v2.y, 0.0);
     let ball1 = Ball::new(2.0);
     let ball2 = Ball::new(target_rad);
-    match contact(&pos1, &ball1, &pos2, &ball2, 0.0).unwrap() {
-        Some(_) => true,
-        None => false,
-    }
+    return contact(&pos1, &ball1, &pos2, &ball2, 0.0).unwrap().is_some();
 }
 
 
@@ -108,6 +104,7 @@ pub enum DetectionTypes {
     Rock,
 }
 
+#[derive(Clone, Copy)]
 pub struct Detection {
     pub distance: f32,
     pub angle: f32,
@@ -124,6 +121,14 @@ impl Detection {
             target_type: ObjectType::Agent,
         }
     }
+    pub fn new_typed(distance: f32, angle: f32, pos: Vec2, target_type: ObjectType) -> Self {
+        Self {
+            distance,
+            angle,
+            pos,
+            target_type,
+        }
+    }
     pub fn new_empty() -> Self {
         Self {
             distance: f32::NAN,
@@ -174,41 +179,60 @@ impl Detections {
     }
 } */
 
+/// Nearest detected object of every type, keyed by the id of the observer.
 pub struct DetectionsMap {
     pub detections: HashMap<u64, Detection>,
     pub sources: HashMap<u64, Detection>,
+    pub obstacles: HashMap<u64, Detection>,
 }
 
 impl DetectionsMap {
     pub fn new() -> Self {
-        Self { detections: HashMap::new(), sources: HashMap::new() }
+        Self { detections: HashMap::new(), sources: HashMap::new(), obstacles: HashMap::new() }
+    }
+    fn get_map(&self, target_type: ObjectType) -> Option<&HashMap<u64, Detection>> {
+        match target_type {
+            ObjectType::Agent => Some(&self.detections),
+            ObjectType::Source => Some(&self.sources),
+            ObjectType::Obstacle => Some(&self.obstacles),
+            ObjectType::Empty => None,
+        }
+    }
+    fn get_map_mut(&mut self, target_type: ObjectType) -> Option<&mut HashMap<u64, Detection>> {
+        match target_type {
+            ObjectType::Agent => Some(&mut self.detections),
+            ObjectType::Source => Some(&mut self.sources),
+            ObjectType::Obstacle => Some(&mut self.obstacles),
+            ObjectType::Empty => None,
+        }
     }
+    /// Keeps the detection if it is closer than the one already stored for its type.
     pub fn add_detection(&mut self, id: u64, detection: Detection) {
-        let old_detection = self.detections.get(&id);
-        let actual = match old_detection {
-            Some(actual_detection) if actual_detection.distance > detection.distance => {
-                detection
-            },
-            Some(actual_detection) if actual_detection.distance <= detection.distance => {
-                Detection::new(actual_detection.distance, actual_detection.angle, actual_detection.pos)
-            },
-            Some(_) => {
-                Detection::new(f32::NAN, f32::NAN, Vec2::NAN)
-            }
-            None => {
-                detection
-            }
+        if detection.is_empty() {
+            return;
+        }
+        let Some(map) = self.get_map_mut(detection.target_type) else {
+            return;
         };
-        self.detections.insert(id, actual);
+        match map.get(&id) {
+            Some(actual_detection) if actual_detection.distance <= detection.distance => {},
+            _ => {
+                map.insert(id, detection);
+            },
+        }
     }
     pub fn clear(&mut self) {
         self.detections.clear();
+        self.sources.clear();
+        self.obstacles.clear();
     }
     pub fn remove_detection(&mut self, id: u64) {
         _ = self.detections.remove(&id);
+        _ = self.sources.remove(&id);
+        _ = self.obstacles.remove(&id);
     }
-    pub fn get_detection(&mut self, id: u64) -> Option<&Detection> {
-        return self.detections.get(&id);
+    pub fn get_detection(&self, id: u64, target_type: ObjectType) -> Option<&Detection> {
+        return self.get_map(target_type)?.get(&id);
     } 
 }
 
@@ -217,6 +241,7 @@ impl DetectionsMap {
 //      **                 CONTACTS                 **
 //      **********************************************
 
+#[derive(Clone, Copy)]
 pub struct Hit {
     pub normal: macroquad::math::Vec2,
     pub overlap: f32,
@@ -250,15 +275,154 @@ pub struct Collisions {
     collisions: Vec<Hit>,
 }
 
+impl Collisions {
+    pub fn new() -> Self {
+        Self { collisions: vec![] }
+    }
+    pub fn add_hit(&mut self, hit: Hit) {
+        self.remove_hit(hit.target_id);
+        self.collisions.push(hit);
+    }
+    pub fn remove_hit(&mut self, target_id: u64) {
+        self.collisions.retain(|hit| hit.target_id != target_id);
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Hit> {
+        return self.collisions.iter();
+    }
+    pub fn count(&self) -> usize {
+        return self.collisions.len();
+    }
+    pub fn is_empty(&self) -> bool {
+        return self.collisions.is_empty();
+    }
+}
+
 pub struct CollisionsList {
-    collisions: HashMap<u32, Collisions>,
+    collisions: HashMap<u64, Collisions>,
+}
+
+impl CollisionsList {
+    pub fn new() -> Self {
+        Self { collisions: HashMap::new() }
+    }
+    pub fn add_hit(&mut self, id: u64, hit: Hit) {
+        self.collisions.entry(id).or_insert(Collisions::new()).add_hit(hit);
+    }
+    pub fn remove_hit(&mut self, id: u64, target_id: u64) {
+        if let Some(collisions) = self.collisions.get_mut(&id) {
+            collisions.remove_hit(target_id);
+            if collisions.is_empty() {
+                self.collisions.remove(&id);
+            }
+        }
+    }
+    /// Forgets an object entirely, both its own hits and hits targeting it.
+    pub fn remove_object(&mut self, id: u64) {
+        _ = self.collisions.remove(&id);
+        for (_, collisions) in self.collisions.iter_mut() {
+            collisions.remove_hit(id);
+        }
+        self.collisions.retain(|_, collisions| !collisions.is_empty());
+    }
+    pub fn get_collisions(&self, id: u64) -> Option<&Collisions> {
+        return self.collisions.get(&id);
+    }
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, u64, Collisions> {
+        return self.collisions.iter();
+    }
+    pub fn clear(&mut self) {
+        self.collisions.clear();
+    }
+}
+
+//      **********************************************
+//      **                 IMPACTS                  **
+//      **********************************************
+
+pub struct ImpactStats {
+    pub wall_pressure: [f32; 4],
+    pub collision_rate: f32,
+    pub impact_energy_rate: f32,
+    pub impact_max: f32,
+    pub impact_mean: f32,
+    impacts: HashMap<u64, f32>,
+    window_impacts: HashMap<u64, f32>,
+    wall_impulses: [f32; 4],
+    collisions_num: u32,
+    impact_energy: f32,
+    time: f32,
+}
+
+impl ImpactStats {
+    const WINDOW: f32 = 1.0;
+
+    pub fn new() -> Self {
+        Self {
+            wall_pressure: [0.0; 4],
+            collision_rate: 0.0,
+            impact_energy_rate: 0.0,
+            impact_max: 0.0,
+            impact_mean: 0.0,
+            impacts: HashMap::new(),
+            window_impacts: HashMap::new(),
+            wall_impulses: [0.0; 4],
+            collisions_num: 0,
+            impact_energy: 0.0,
+            time: 0.0,
+        }
+    }
+    pub fn add_wall_impulse(&mut self, wall: usize, impulse: f32) {
+        self.wall_impulses[wall] += impulse;
+    }
+    pub fn add_impact(&mut self, id: u64, impulse: f32) {
+        *self.impacts.entry(id).or_insert(0.0) += impulse;
+    }
+    pub fn add_collision(&mut self, energy: f32) {
+        self.collisions_num += 1;
+        self.impact_energy += energy;
+    }
+    /// Advances the measuring window and publishes averaged values when it is full.
+    pub fn update(&mut self, dt: f32, wall_lengths: [f32; 4]) {
+        self.time += dt;
+        if self.time < Self::WINDOW {
+            return;
+        }
+        for (i, length) in wall_lengths.iter().enumerate() {
+            self.wall_pressure[i] = self.wall_impulses[i] / self.time / length;
+        }
+        self.collision_rate = self.collisions_num as f32 / self.time;
+        self.impact_energy_rate = self.impact_energy / self.time;
+        self.impact_max = self.impacts.values().fold(0.0, |a, b| a.max(*b));
+        self.impact_mean = if self.impacts.is_empty() { 0.0 } else { self.impacts.values().sum::<f32>() / self.impacts.len() as f32 };
+        self.window_impacts = std::mem::take(&mut self.impacts);
+        self.wall_impulses = [0.0; 4];
+        self.collisions_num = 0;
+        self.impact_energy = 0.0;
+        self.time = 0.0;
+    }
+    /// Impulse taken by an agent during the last window.
+    pub fn get_impact(&self, id: u64) -> f32 {
+        return self.window_impacts.get(&id).copied().unwrap_or(0.0);
+    }
+    pub fn remove(&mut self, id: u64) {
+        _ = self.impacts.remove(&id);
+        _ = self.window_impacts.remove(&id);
+    }
+    /// Largest impulse taken by one agent during the last window.
+    pub fn max_impact(&self) -> f32 {
+        return self.impact_max;
+    }
+    /// Mean impulse per agent hit during the last window.
+    pub fn mean_impact(&self) -> f32 {
+        return self.impact_mean;
+    }
 }
 
 //      **********************************************
 //      **               OBJECT TYPE                **
 //      **********************************************
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum ObjectType {
     Empty,
     Agent,
@@ -1,5 +1,6 @@
 
 #![allow(unused)]
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
 
 mod sim;
 mod consts;
@@ -13,6 +14,19 @@ mod progress_bar;
 mod world;
 mod source;
 mod camera;
+mod force_field;
+mod potential;
+mod thermostat;
+mod rock;
+mod species;
+mod reaction;
+mod compound;
+mod energy;
+mod scalar_field;
+mod sensor;
+mod actuator;
+mod genome;
+mod evolution;
 
 use std::{time, thread};
 use macroquad::miniquad::conf::Icon;
@@ -50,11 +64,11 @@ async fn main() {
         sim.process_ui();
         if sim.is_running() {
             sim.update();
-            sim.draw();
         }
         else {
             sim.signals_check();
         }
+        sim.draw();
         sim.draw_ui();
         next_frame().await;
     }
@@ -1,24 +1,254 @@
+#![allow(unused)]
+
 use rand::{Rng, thread_rng};
+use crate::util::random_gauss;
 
 
-pub struct DummyNetwork {
-    outputs: usize
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Activation {
+    Linear,
+    Tanh,
+    ReLU,
+    Sigmoid,
 }
 
-impl DummyNetwork {
-    pub fn new(outputs_num: usize) -> Self {
-        Self {
-            outputs: outputs_num,
+impl Activation {
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            Activation::Linear => x,
+            Activation::Tanh => x.tanh(),
+            Activation::ReLU => x.max(0.0),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activation::Linear => "linear",
+            Activation::Tanh => "tanh",
+            Activation::ReLU => "relu",
+            Activation::Sigmoid => "sigmoid",
         }
     }
 
-    pub fn analize(&self) -> Vec<f32> {
-        let mut outputs: Vec<f32> = vec![];
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Activation::Linear),
+            "tanh" => Some(Activation::Tanh),
+            "relu" => Some(Activation::ReLU),
+            "sigmoid" => Some(Activation::Sigmoid),
+            _ => None,
+        }
+    }
+}
+
+pub fn activation_variants() -> [Activation; 4] {
+    return [Activation::Linear, Activation::Tanh, Activation::ReLU, Activation::Sigmoid];
+}
+
+/// Fully connected layer. Weights are stored row by row, one row per output.
+#[derive(Clone)]
+pub struct Layer {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub weights: Vec<f32>,
+    pub biases: Vec<f32>,
+    pub activation: Activation,
+}
+
+impl Layer {
+    pub fn new(inputs: usize, outputs: usize, activation: Activation) -> Self {
         let mut rng = thread_rng();
-        for _ in 0..self.outputs {
-           let out = rng.gen_range(-1.0..1.0); 
-           outputs.push(out);
+        let scale = (1.0 / inputs.max(1) as f32).sqrt();
+        Self {
+            inputs: inputs,
+            outputs: outputs,
+            weights: (0..inputs*outputs).map(|_| rng.gen_range(-1.0..1.0) * scale).collect(),
+            biases: vec![0.0; outputs],
+            activation: activation,
+        }
+    }
+
+    pub fn forward(&self, inputs: &[f32]) -> Vec<f32> {
+        let mut outputs: Vec<f32> = Vec::with_capacity(self.outputs);
+        for o in 0..self.outputs {
+            let row = &self.weights[o*self.inputs..(o+1)*self.inputs];
+            let sum: f32 = row.iter().zip(inputs.iter()).map(|(w, x)| w * x).sum();
+            outputs.push(self.activation.apply(sum + self.biases[o]));
         }
         return outputs;
     }
-}
\ No newline at end of file
+
+    pub fn params_num(&self) -> usize {
+        return self.weights.len() + self.biases.len();
+    }
+}
+
+/// Feed-forward network with one activation for hidden layers and one for the output.
+#[derive(Clone)]
+pub struct Network {
+    pub layers: Vec<Layer>,
+}
+
+impl Network {
+    /// `sizes` lists the number of neurons from the input layer to the output layer.
+    pub fn new(sizes: &[usize], hidden: Activation, output: Activation) -> Self {
+        let mut layers: Vec<Layer> = vec![];
+        for i in 1..sizes.len() {
+            let activation = if i == sizes.len()-1 { output } else { hidden };
+            layers.push(Layer::new(sizes[i-1], sizes[i], activation));
+        }
+        Self { layers }
+    }
+
+    pub fn inputs_num(&self) -> usize {
+        return self.layers.first().map_or(0, |l| l.inputs);
+    }
+
+    pub fn outputs_num(&self) -> usize {
+        return self.layers.last().map_or(0, |l| l.outputs);
+    }
+
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = vec![self.inputs_num()];
+        sizes.extend(self.layers.iter().map(|l| l.outputs));
+        return sizes;
+    }
+
+    /// Missing inputs are treated as zeros, extra inputs are ignored.
+    pub fn forward(&self, inputs: &[f32]) -> Vec<f32> {
+        let mut values: Vec<f32> = (0..self.inputs_num()).map(|i| inputs.get(i).copied().unwrap_or(0.0)).collect();
+        for layer in self.layers.iter() {
+            values = layer.forward(&values);
+        }
+        return values;
+    }
+
+    /// Adds gaussian noise of given strength to each weight and bias with probability `rate`.
+    pub fn mutate(&mut self, rate: f32, strength: f32) {
+        let mut rng = thread_rng();
+        for layer in self.layers.iter_mut() {
+            for param in layer.weights.iter_mut().chain(layer.biases.iter_mut()) {
+                if rng.gen_range(0.0..1.0) < rate {
+                    *param += random_gauss() * strength;
+                }
+            }
+        }
+    }
+
+    pub fn params_num(&self) -> usize {
+        return self.layers.iter().map(|l| l.params_num()).sum();
+    }
+
+    /// All weights and biases as one flat vector, layer by layer.
+    pub fn get_params(&self) -> Vec<f32> {
+        let mut params: Vec<f32> = Vec::with_capacity(self.params_num());
+        for layer in self.layers.iter() {
+            params.extend_from_slice(&layer.weights);
+            params.extend_from_slice(&layer.biases);
+        }
+        return params;
+    }
+
+    pub fn set_params(&mut self, params: &[f32]) -> Result<(), String> {
+        if params.len() != self.params_num() {
+            return Err(format!("expected {} parameters, found {}", self.params_num(), params.len()));
+        }
+        let mut offset = 0;
+        for layer in self.layers.iter_mut() {
+            let w = layer.weights.len();
+            layer.weights.copy_from_slice(&params[offset..offset+w]);
+            offset += w;
+            let b = layer.biases.len();
+            layer.biases.copy_from_slice(&params[offset..offset+b]);
+            offset += b;
+        }
+        return Ok(());
+    }
+
+    /// Text form: a header line with layer sizes, then one line per layer with its
+    /// activation followed by weights and biases.
+    pub fn serialize(&self) -> String {
+        let sizes: Vec<String> = self.sizes().iter().map(|s| s.to_string()).collect();
+        let mut text = format!("network {}\n", sizes.join(" "));
+        for layer in self.layers.iter() {
+            let params: Vec<String> = layer.weights.iter().chain(layer.biases.iter()).map(|p| p.to_string()).collect();
+            text.push_str(&format!("{} {}\n", layer.activation.name(), params.join(" ")));
+        }
+        return text;
+    }
+
+    pub fn deserialize(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+        let header = lines.next().ok_or("empty network description")?;
+        let mut header_fields = header.split_whitespace();
+        if header_fields.next() != Some("network") {
+            return Err("missing network header".to_string());
+        }
+        let sizes: Vec<usize> = header_fields.map(|s| s.parse::<usize>().map_err(|_| format!("invalid layer size '{}'", s))).collect::<Result<_, _>>()?;
+        if sizes.len() < 2 {
+            return Err("network needs at least two layers".to_string());
+        }
+        let mut layers: Vec<Layer> = vec![];
+        for i in 1..sizes.len() {
+            let line = lines.next().ok_or(format!("missing layer {}", i))?;
+            let mut fields = line.split_whitespace();
+            let name = fields.next().unwrap_or("");
+            let activation = Activation::from_name(name).ok_or(format!("unknown activation '{}'", name))?;
+            let params: Vec<f32> = fields.map(|p| p.parse::<f32>().map_err(|_| format!("invalid parameter '{}'", p))).collect::<Result<_, _>>()?;
+            let (inputs, outputs) = (sizes[i-1], sizes[i]);
+            if params.len() != inputs*outputs + outputs {
+                return Err(format!("layer {}: expected {} parameters, found {}", i, inputs*outputs + outputs, params.len()));
+            }
+            layers.push(Layer {
+                inputs: inputs,
+                outputs: outputs,
+                weights: params[..inputs*outputs].to_vec(),
+                biases: params[inputs*outputs..].to_vec(),
+                activation: activation,
+            });
+        }
+        return Ok(Self { layers });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_is_deterministic() {
+        let network = Network::new(&[4, 3, 2], Activation::Tanh, Activation::Sigmoid);
+        let inputs = [0.5, -1.0, 0.25, 2.0];
+        let outputs = network.forward(&inputs);
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs, network.forward(&inputs));
+        assert!(outputs.iter().all(|o| *o > 0.0 && *o < 1.0));
+    }
+
+    #[test]
+    fn missing_inputs_are_zeros() {
+        let network = Network::new(&[3, 2], Activation::Linear, Activation::Linear);
+        assert_eq!(network.forward(&[1.0]), network.forward(&[1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let network = Network::new(&[5, 4, 3], Activation::ReLU, Activation::Tanh);
+        let restored = Network::deserialize(&network.serialize()).unwrap();
+        assert_eq!(restored.sizes(), network.sizes());
+        assert_eq!(restored.get_params(), network.get_params());
+        let activations: Vec<Activation> = restored.layers.iter().map(|l| l.activation).collect();
+        assert_eq!(activations, vec![Activation::ReLU, Activation::Tanh]);
+        let inputs = [0.1, 0.2, -0.3, 0.4, -0.5];
+        assert_eq!(restored.forward(&inputs), network.forward(&inputs));
+    }
+
+    #[test]
+    fn set_params_checks_length() {
+        let mut network = Network::new(&[2, 2], Activation::Linear, Activation::Linear);
+        assert!(network.set_params(&[0.0; 5]).is_err());
+        assert!(network.set_params(&[1.0; 6]).is_ok());
+        assert_eq!(network.forward(&[1.0, 1.0]), vec![3.0, 3.0]);
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::hash_map::IterMut;
 use std::f32::consts::PI;
 
 use macroquad::{prelude::*, color}; 
-use parry2d::shape::{Cuboid, Ball};
+use parry2d::shape::Ball;
 use ::rand::{Rng, thread_rng};
 use rapier2d::prelude::*;
 use crate::kinetic::{Detection, contact_circles};
@@ -14,13 +14,22 @@ use crate::consts::*;
 use crate::timer::*;
 use crate::neuro::*;
 use crate::world::*;
+use crate::species::*;
+use crate::sensor::*;
+use crate::actuator::*;
+use crate::genome::*;
+
+#[derive(Clone)]
+pub enum StaticShape {
+    Box { width: f32, height: f32 },
+    Circle { radius: f32 },
+    Polygon { points: Vec<Vec2> },
+}
 
 pub struct StaticElement {
     pub pos: Vec2,
-    pub width: f32,
-    pub height: f32,
+    pub shape: StaticShape,
     pub color: color::Color,
-    pub shape: Cuboid,
     pub physics_handle: Option<RigidBodyHandle>,
 }
 
@@ -28,10 +37,27 @@ impl StaticElement {
     pub fn new(position: Vec2, w: f32, h: f32, color: color::Color) -> Self {
         Self { 
             pos: position, 
-            width: w, 
-            height: h, 
+            shape: StaticShape::Box { width: w, height: h }, 
+            color: color, 
+            physics_handle: None 
+        }
+    }
+
+    pub fn new_circle(position: Vec2, radius: f32, color: color::Color) -> Self {
+        Self { 
+            pos: position, 
+            shape: StaticShape::Circle { radius: radius }, 
+            color: color, 
+            physics_handle: None 
+        }
+    }
+
+    /// Polygon points are given relative to the element position.
+    pub fn new_polygon(position: Vec2, points: Vec<Vec2>, color: color::Color) -> Self {
+        Self { 
+            pos: position, 
+            shape: StaticShape::Polygon { points: points }, 
             color: color, 
-            shape: Cuboid { half_extents: vector![w, h] }, 
             physics_handle: None 
         }
     }
@@ -39,20 +65,22 @@ impl StaticElement {
     pub fn draw(&self) {
         let x0 = self.pos.x;
         let y0 = self.pos.y;
-        let w = self.width;
-        let h = self.height;
-        draw_rectangle(x0, y0, w, h, self.color);
-    }
-
-    pub fn update2(&mut self, physics: &mut World) {
-        match self.physics_handle {
-            Some(handle) => {
-                let physics_data = physics.get_physics_data(handle);
-                self.pos = physics_data.position;
+        match &self.shape {
+            StaticShape::Box { width, height } => {
+                draw_rectangle(x0-width/2.0, y0-height/2.0, *width, *height, self.color);
+            },
+            StaticShape::Circle { radius } => {
+                draw_circle(x0, y0, *radius, self.color);
+            },
+            StaticShape::Polygon { points } => {
+                let n = points.len();
+                for i in 0..n {
+                    let p1 = self.pos + points[i];
+                    let p2 = self.pos + points[(i+1)%n];
+                    draw_triangle(self.pos, p1, p2, self.color);
+                }
             },
-            None => {},
         }
-
     }
 }
 
@@ -65,13 +93,29 @@ pub struct Molecule {
     pub color: color::Color,
     pub shape: Ball,
     pub field_range: i32,
+    pub charge: f32,
+    pub species: usize,
+    pub decay_timer: Option<Timer>,
+    pub eng: f32,
+    pub max_eng: f32,
+    pub lifetime: f32,
+    pub alife: bool,
+    pub rays: Vec<RayHit>,
+    pub inputs: Vec<f32>,
+    pub genome: Genome,
+    pub actions: Actions,
     pub physics_handle: Option<RigidBodyHandle>,
 }
 
 impl Molecule {
-    pub fn new() -> Self {
-        let s = (rand::gen_range(MOLECULE_SIZE_MIN, MOLECULE_SIZE_MAX) as f32).round();
-        let motor = thread_rng().gen_bool(1.0);
+    /// Builds a molecule whose traits and brain are fully determined by the genome.
+    /// Only the state (position, rotation, speed) is random. An unknown species id is
+    /// clamped to a valid one.
+    pub fn from_genome(genome: &Genome, registry: &SpeciesRegistry) -> Self {
+        let mut genome = genome.clone();
+        genome.species = registry.clamp_id(genome.species);
+        let species = registry.get_clamped(genome.species);
+        let s = genome.size;
         Self {
             pos: random_position(WORLD_W, WORLD_H),
             //pos: Vec2::new(0.0, 0.0),
@@ -79,28 +123,90 @@ impl Molecule {
             vel: rand::gen_range(0.0, 1.0)*MOLECULE_SPEED,
             ang_vel: 0.0,
             size: s,
-            color: random_color(),
+            color: genome.color,
             shape: Ball { radius: s },
-            field_range: rand::gen_range(16, 96),
+            field_range: genome.field_range as i32,
+            charge: genome.charge,
+            species: genome.species,
+            decay_timer: match species.is_stable() {
+                true => None,
+                false => Some(Timer::new(species.random_lifetime(), false, true, false)),
+            },
+            eng: s.powi(2)*MOLECULE_ENG*0.5,
+            max_eng: s.powi(2)*MOLECULE_ENG,
+            lifetime: 0.0,
+            alife: true,
+            rays: vec![],
+            inputs: vec![],
+            genome: genome,
+            actions: Actions::new(),
             physics_handle: None,
         }
     }
+    /// Charge is kept in the genome as well, so the molecule can be rebuilt from it.
+    pub fn set_charge(&mut self, charge: f32) {
+        self.charge = charge.clamp(-MAX_CHARGE, MAX_CHARGE);
+        self.genome.charge = self.charge;
+    }
+
     pub fn draw(&self) {
         let x0 = self.pos.x;
         let y0 = self.pos.y;
         draw_circle(x0, y0, self.size, self.color);
+        if self.charge != 0.0 {
+            draw_circle_lines(x0, y0, self.size+1.0, 1.0, charge_color(self.charge));
+        }
         draw_circle_lines(x0, y0, self.field_range as f32, 0.5, GRAY);
     }
 
+    /// Feeds sensor inputs, energy level and speed through the brain and decodes actions.
+    pub fn think(&mut self, max_speed: f32) {
+        let mut inputs = self.inputs.clone();
+        inputs.resize(inputs_num(SENSOR_RAYS), 0.0);
+        inputs.push(self.eng / self.max_eng);
+        inputs.push((self.vel / max_speed.max(1.0)).min(1.0));
+        self.actions = Actions::from_outputs(&self.genome.network.forward(&inputs));
+    }
+
+    /// Burns energy for staying alive (scaled by size) and for moving.
+    pub fn metabolize(&mut self, dt: f32) {
+        self.lifetime += dt;
+        let cost = BASE_METABOLISM * self.size.powi(2) + MOVE_COST * self.vel * self.size;
+        self.eng -= cost * dt;
+        if self.eng <= 0.0 {
+            self.eng = 0.0;
+            self.alife = false;
+        }
+    }
+
+    pub fn is_ready_to_split(&self) -> bool {
+        return self.eng >= self.max_eng * FISSION_ENG_RATIO;
+    }
+
+    /// Creates a daughter with half of the parent energy from a mutated copy of the parent genome.
+    pub fn daughter(&self, registry: &SpeciesRegistry, mutation_rate: f32, mutation_magnitude: f32) -> Self {
+        let mut genome = self.genome.clone();
+        genome.mutate(mutation_rate, mutation_magnitude);
+        genome.charge = 0.0;
+        let mut daughter = Molecule::from_genome(&genome, registry);
+        daughter.pos = self.pos;
+        daughter.rot = self.rot;
+        daughter.eng = (self.eng / 2.0).min(daughter.max_eng);
+        return daughter;
+    }
+
+    pub fn add_eng(&mut self, eng: f32) {
+        self.eng = (self.eng + eng).min(self.max_eng);
+    }
+
     pub fn update2(&mut self, physics: &mut World) {
-        match self.physics_handle {
-            Some(handle) => {
-                let physics_data = physics.get_physics_data(handle);
-                self.pos = physics_data.position;
-                self.rot = physics_data.rotation;
-                self.field_range = physics_data.field_radius as i32;
-            },
-            None => {},
+        if let Some(handle) = self.physics_handle {
+            let physics_data = physics.get_physics_data(handle);
+            self.pos = physics_data.position;
+            self.rot = physics_data.rotation;
+            self.field_range = physics_data.field_radius as i32;
+            self.vel = physics_data.velocity.length();
+            self.ang_vel = physics_data.angular_velocity;
         }
 
     }
@@ -108,6 +214,19 @@ impl Molecule {
 
 
 
+/// Sensor inputs plus energy level and speed.
+pub fn brain_inputs_num() -> usize {
+    return inputs_num(SENSOR_RAYS) + 2;
+}
+
+pub struct DeathEvent {
+    pub id: u64,
+    pub species: usize,
+    pub position: Vec2,
+    pub lifetime: f32,
+    pub time: f64,
+}
+
 pub struct MoleculesBox {
     pub molecules: HashMap<u64, Molecule>
 }
@@ -119,16 +238,17 @@ impl MoleculesBox {
         }
     }
 
-    pub fn add_many_molecules(&mut self, molecules_num: usize, physics_world: &mut World) {
+    pub fn add_many_molecules(&mut self, molecules_num: usize, registry: &SpeciesRegistry, physics_world: &mut World) {
         for _ in 0..molecules_num {
-            let molecule = Molecule::new();
-            _ = self.add_molecule(molecule, physics_world);
+            let molecule = Molecule::from_genome(&Genome::random(registry.random_id(), registry), registry);
+            _ = self.add_molecule(molecule, registry, physics_world);
         }
     }
 
-    pub fn add_molecule(&mut self, mut molecule: Molecule, physics_world: &mut World) -> u64 {
+    pub fn add_molecule(&mut self, mut molecule: Molecule, registry: &SpeciesRegistry, physics_world: &mut World) -> u64 {
         let key: u64 = thread_rng().gen::<u64>();
-        let handle = physics_world.add_circle_body(&molecule.pos, molecule.size, molecule.field_range as f32);
+        let species = molecule.genome.express_species(registry);
+        let handle = physics_world.add_circle_body(key, &molecule.pos, molecule.size, molecule.field_range as f32, &species);
         molecule.physics_handle = Some(handle);
         self.molecules.insert(key, molecule);
         return key;
@@ -138,15 +258,24 @@ impl MoleculesBox {
         return self.molecules.get(&id);
     }
 
-    pub fn remove(&mut self, id: u64) {
-        self.molecules.remove(&id);
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut Molecule> {
+        return self.molecules.get_mut(&id);
     }
 
-    pub fn get_iter(&self) -> Iter<u64, Molecule> {
+    /// Removes the molecule together with its rigid body, colliders and joints.
+    pub fn remove(&mut self, id: u64, physics_world: &mut World) -> Option<Molecule> {
+        let molecule = self.molecules.remove(&id)?;
+        if let Some(handle) = molecule.physics_handle {
+            physics_world.remove_physics_object(handle);
+        }
+        return Some(molecule);
+    }
+
+    pub fn get_iter(&self) -> Iter<'_, u64, Molecule> {
         return self.molecules.iter();
     }
     
-    pub fn get_iter_mut(&mut self) -> IterMut<u64, Molecule> {
+    pub fn get_iter_mut(&mut self) -> IterMut<'_, u64, Molecule> {
         return self.molecules.iter_mut();
     }
 
@@ -168,25 +297,45 @@ impl StaticElementBox {
 
     pub fn add_element(&mut self, mut static_element: StaticElement, physics_world: &mut World) -> u64 {
         let key: u64 = thread_rng().gen::<u64>();
-        let handle = physics_world.add_static_body(&static_element.pos, static_element.width, static_element.height);
+        let handle = physics_world.add_static_body(key, &static_element.pos, &static_element.shape);
         static_element.physics_handle = Some(handle);
         self.elements.insert(key, static_element);
         return key;
     }
 
+    /// Registers all elements again in a freshly created physics world.
+    pub fn rebuild(&mut self, physics_world: &mut World) {
+        for (id, static_element) in self.elements.iter_mut() {
+            let handle = physics_world.add_static_body(*id, &static_element.pos, &static_element.shape);
+            static_element.physics_handle = Some(handle);
+        }
+    }
+
     pub fn get(&self, id: u64) -> Option<&StaticElement> {
         return self.elements.get(&id);
     }
 
-    pub fn remove(&mut self, id: u64) {
-        self.elements.remove(&id);
+    pub fn remove(&mut self, id: u64, physics_world: &mut World) {
+        if let Some(static_element) = self.elements.remove(&id) {
+            if let Some(handle) = static_element.physics_handle {
+                physics_world.remove_physics_object(handle);
+            }
+        }
+    }
+
+    pub fn clear(&mut self, physics_world: &mut World) {
+        for (_, static_element) in self.elements.drain() {
+            if let Some(handle) = static_element.physics_handle {
+                physics_world.remove_physics_object(handle);
+            }
+        }
     }
 
-    pub fn get_iter(&self) -> Iter<u64, StaticElement> {
+    pub fn get_iter(&self) -> Iter<'_, u64, StaticElement> {
         return self.elements.iter();
     }
     
-    pub fn get_iter_mut(&mut self) -> IterMut<u64, StaticElement> {
+    pub fn get_iter_mut(&mut self) -> IterMut<'_, u64, StaticElement> {
         return self.elements.iter_mut();
     }
 
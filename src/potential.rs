@@ -0,0 +1,128 @@
+#![allow(unused)]
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Potential {
+    None,
+    LennardJones { epsilon: f32, sigma: f32 },
+    Coulomb { k: f32 },
+    Morse { depth: f32, width: f32, r0: f32 },
+    SoftSphere { k: f32, sigma: f32 },
+}
+
+impl Potential {
+    pub fn default_lennard_jones() -> Self {
+        return Potential::LennardJones { epsilon: 50.0, sigma: 12.0 };
+    }
+
+    pub fn default_coulomb() -> Self {
+        return Potential::Coulomb { k: 50000.0 };
+    }
+
+    pub fn default_morse() -> Self {
+        return Potential::Morse { depth: 50.0, width: 0.2, r0: 14.0 };
+    }
+
+    pub fn default_soft_sphere() -> Self {
+        return Potential::SoftSphere { k: 500.0, sigma: 16.0 };
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Potential::None => "None",
+            Potential::LennardJones { .. } => "Lennard-Jones",
+            Potential::Coulomb { .. } => "Coulomb",
+            Potential::Morse { .. } => "Morse",
+            Potential::SoftSphere { .. } => "Soft Sphere",
+        }
+    }
+
+    /// Distance below which the force stops growing, to keep close contacts stable.
+    fn min_distance(&self) -> f32 {
+        match *self {
+            Potential::LennardJones { sigma, .. } => (0.7*sigma).max(1.0),
+            _ => 1.0,
+        }
+    }
+
+    /// Radial force magnitude at distance r. Positive values push bodies apart.
+    pub fn force(&self, r: f32) -> f32 {
+        let r = r.max(self.min_distance());
+        match *self {
+            Potential::None => 0.0,
+            Potential::LennardJones { epsilon, sigma } => {
+                let sr6 = (sigma/r).powi(6);
+                return 24.0 * epsilon / r * (2.0*sr6*sr6 - sr6);
+            },
+            Potential::Coulomb { k } => {
+                return k / r.powi(2);
+            },
+            Potential::Morse { depth, width, r0 } => {
+                let e = (-width*(r-r0)).exp();
+                return 2.0 * depth * width * (e*e - e);
+            },
+            Potential::SoftSphere { k, sigma } => {
+                if r < sigma {
+                    return k * (1.0 - r/sigma);
+                }
+                return 0.0;
+            },
+        }
+    }
+
+    /// Pair energy at distance r, consistent with `force` (force = -dE/dr). Below
+    /// the minimum distance the force is constant, so the energy grows linearly.
+    pub fn energy(&self, r: f32) -> f32 {
+        let rc = self.min_distance();
+        if r < rc {
+            return self.energy(rc) + self.force(rc) * (rc - r);
+        }
+        match *self {
+            Potential::None => 0.0,
+            Potential::LennardJones { epsilon, sigma } => {
+                let sr6 = (sigma/r).powi(6);
+                return 4.0 * epsilon * (sr6*sr6 - sr6);
+            },
+            Potential::Coulomb { k } => {
+                return k / r;
+            },
+            Potential::Morse { depth, width, r0 } => {
+                let e = (-width*(r-r0)).exp();
+                return depth * (e*e - 2.0*e);
+            },
+            Potential::SoftSphere { k, sigma } => {
+                if r < sigma {
+                    return 0.5 * k * sigma * (1.0 - r/sigma).powi(2);
+                }
+                return 0.0;
+            },
+        }
+    }
+}
+
+pub fn potential_variants() -> [Potential; 5] {
+    return [
+        Potential::None,
+        Potential::default_lennard_jones(),
+        Potential::default_coulomb(),
+        Potential::default_morse(),
+        Potential::default_soft_sphere(),
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn force_is_minus_energy_gradient() {
+        let h = 1e-2;
+        for potential in potential_variants() {
+            for r in [0.5, 2.0, 5.0, 8.0, 10.0, 14.0, 20.0, 30.0] {
+                let gradient = (potential.energy(r + h) - potential.energy(r - h)) / (2.0 * h);
+                let force = potential.force(r);
+                assert!((force + gradient).abs() <= 1e-2 * force.abs().max(1.0), "{} at {}: force {} gradient {}", potential.name(), r, force, gradient);
+            }
+        }
+    }
+}
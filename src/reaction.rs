@@ -0,0 +1,213 @@
+#![allow(unused)]
+
+use std::fs;
+use macroquad::prelude::*;
+use crate::species::*;
+use crate::util::*;
+
+
+/// A + B -> products, fired when A and B collide faster than the activation speed.
+#[derive(Clone)]
+pub struct ReactionRule {
+    pub reactants: [usize; 2],
+    pub products: Vec<usize>,
+    pub activation_speed: f32,
+    pub probability: f32,
+    /// Energy released (positive) or absorbed (negative) by the reaction.
+    pub energy: f32,
+}
+
+impl ReactionRule {
+    fn from_line(line: &str, registry: &SpeciesRegistry) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != 6 {
+            return Err(format!("expected 6 fields, found {}", fields.len()));
+        }
+        let species = |name: &str| -> Result<usize, String> {
+            return registry.find(name).ok_or(format!("unknown species '{}'", name));
+        };
+        let number = |i: usize| -> Result<f32, String> {
+            return fields[i].parse::<f32>().map_err(|_| format!("invalid number '{}'", fields[i]));
+        };
+        let mut products: Vec<usize> = vec![];
+        for name in fields[2].split_whitespace() {
+            products.push(species(name)?);
+        }
+        if products.is_empty() {
+            return Err("reaction without products".to_string());
+        }
+        Ok(Self {
+            reactants: [species(fields[0])?, species(fields[1])?],
+            products,
+            activation_speed: number(3)?,
+            probability: number(4)?,
+            energy: number(5)?,
+        })
+    }
+
+    pub fn matches(&self, species1: usize, species2: usize) -> bool {
+        return (self.reactants[0] == species1 && self.reactants[1] == species2)
+            || (self.reactants[0] == species2 && self.reactants[1] == species1);
+    }
+}
+
+pub struct ReactionRules {
+    rules: Vec<ReactionRule>,
+}
+
+impl ReactionRules {
+    pub fn new() -> Self {
+        Self { rules: vec![] }
+    }
+
+    pub fn parse(text: &str, registry: &SpeciesRegistry) -> Result<Self, String> {
+        let mut rules: Vec<ReactionRule> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rule = ReactionRule::from_line(line, registry).map_err(|e| format!("line {}: {}", i+1, e))?;
+            rules.push(rule);
+        }
+        return Ok(Self { rules });
+    }
+
+    /// Loads the reactions file and falls back to the copy built into the binary. The
+    /// error is returned along with the built-in rules so it can be shown to the user.
+    pub fn load_or_default(path: &str, registry: &SpeciesRegistry) -> (Self, Option<String>) {
+        let loaded = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path, e))
+            .and_then(|text| Self::parse(&text, registry));
+        match loaded {
+            Ok(rules) => (rules, None),
+            Err(err) => {
+                let rules = Self::parse(include_str!("../assets/data/reactions.csv"), registry).unwrap_or(Self::new());
+                return (rules, Some(format!("reaction rules: {}, using built-in rules", err)));
+            },
+        }
+    }
+
+    pub fn find(&self, species1: usize, species2: usize) -> Option<&ReactionRule> {
+        return self.rules.iter().find(|rule| rule.matches(species1, species2));
+    }
+
+    pub fn count(&self) -> usize {
+        return self.rules.len();
+    }
+}
+
+/// Splits total momentum among bodies of given masses so that the kinetic energy
+/// of their motion relative to the centre of mass equals `internal_energy`.
+pub fn split_momentum(masses: &[f32], momentum: Vec2, internal_energy: f32) -> Vec<Vec2> {
+    let total_mass: f32 = masses.iter().sum();
+    let v_cm = momentum / total_mass;
+    if masses.len() < 2 || internal_energy <= 0.0 {
+        return vec![v_cm; masses.len()];
+    }
+    let mut relative: Vec<Vec2> = masses.iter().map(|m| random_unit_vec2() / *m).collect();
+    let drift = masses.iter().zip(relative.iter()).fold(Vec2::ZERO, |acc, (m, u)| acc + *u * *m) / total_mass;
+    for u in relative.iter_mut() {
+        *u -= drift;
+    }
+    let energy: f32 = masses.iter().zip(relative.iter()).map(|(m, u)| 0.5 * m * u.length_squared()).sum();
+    let scale = if energy > 0.0 { (internal_energy / energy).sqrt() } else { 0.0 };
+    return relative.iter().map(|u| v_cm + *u * scale).collect();
+}
+
+/// Velocities of reaction products and the energy balance of the event.
+pub struct ProductsMotion {
+    pub velocities: Vec<Vec2>,
+    /// Chemical energy released by the event (negative when it is absorbed).
+    pub released: f32,
+    /// Part of the released energy and the reactants' kinetic energy that the products
+    /// cannot carry without breaking momentum conservation. It leaves as heat.
+    pub dissipated: f32,
+}
+
+/// Shares the reactants' momentum and kinetic energy plus `energy` among products.
+/// A single product has to keep the momentum, so it only moves with the centre of
+/// mass and everything else is dissipated.
+pub fn products_motion(masses: &[f32], momentum: Vec2, kinetic_energy: f32, energy: f32) -> ProductsMotion {
+    let total_mass: f32 = masses.iter().sum();
+    let (velocities, gained) = if total_mass <= 0.0 {
+        (vec![], -kinetic_energy)
+    } else {
+        let drift_energy = 0.5 * momentum.length_squared() / total_mass;
+        let internal_energy = if masses.len() < 2 { 0.0 } else { (kinetic_energy + energy - drift_energy).max(0.0) };
+        (split_momentum(masses, momentum, internal_energy), drift_energy + internal_energy - kinetic_energy)
+    };
+    let dissipated = (energy - gained).max(0.0);
+    return ProductsMotion { velocities, released: gained + dissipated, dissipated };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> SpeciesRegistry {
+        return SpeciesRegistry::parse("Sodium, Na, 6.0, 0.8, #AB5CF2, 32, 0.9, 0.1, 1
+Chlorine, Cl, 5.5, 1.1, #1FF01F, 36, 0.9, 0.1, 1
+Salt, NaCl, 7.0, 1.0, #E0E0E0, 40, 0.9, 0.1, 0").unwrap();
+    }
+
+    #[test]
+    fn parses_rules_in_both_orders() {
+        let registry = registry();
+        let rules = ReactionRules::parse("# comment\nNa, Cl, NaCl, 10, 0.8, 2000\n", &registry).unwrap();
+        assert_eq!(rules.count(), 1);
+        let (na, cl) = (registry.find("Na").unwrap(), registry.find("Cl").unwrap());
+        let rule = rules.find(cl, na).unwrap();
+        assert_eq!(rule.products, vec![registry.find("NaCl").unwrap()]);
+        assert_eq!(rule.energy, 2000.0);
+        assert!(rules.find(na, na).is_none());
+        assert!(ReactionRules::parse("Na, Xe, NaCl, 10, 0.8, 2000", &registry).is_err());
+        assert!(ReactionRules::parse("Na, Cl, , 10, 0.8, 2000", &registry).is_err());
+    }
+
+    #[test]
+    fn split_momentum_conserves_momentum_and_sets_energy() {
+        let masses = [1.0, 2.0, 3.0];
+        let momentum = Vec2::new(3.0, -1.0);
+        let velocities = split_momentum(&masses, momentum, 50.0);
+        let total: Vec2 = masses.iter().zip(velocities.iter()).fold(Vec2::ZERO, |acc, (m, v)| acc + *v * *m);
+        assert!((total - momentum).length() < 1e-3);
+        let v_cm = momentum / 6.0;
+        let internal: f32 = masses.iter().zip(velocities.iter()).map(|(m, v)| 0.5 * m * (*v - v_cm).length_squared()).sum();
+        assert!((internal - 50.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn split_momentum_without_energy_moves_together() {
+        let velocities = split_momentum(&[1.0, 3.0], Vec2::new(4.0, 0.0), 0.0);
+        assert_eq!(velocities, vec![Vec2::new(1.0, 0.0); 2]);
+    }
+
+    #[test]
+    fn single_product_dissipates_exothermic_energy() {
+        // Two unit masses colliding head-on merge into one product at rest.
+        let motion = products_motion(&[2.0], Vec2::ZERO, 1.0, 2000.0);
+        assert_eq!(motion.velocities, vec![Vec2::ZERO]);
+        assert_eq!(motion.released, 2000.0);
+        assert_eq!(motion.dissipated, 2001.0);
+        let kinetic_change = -1.0;
+        assert_eq!(motion.released - motion.dissipated, kinetic_change);
+    }
+
+    #[test]
+    fn several_products_carry_exothermic_energy() {
+        let momentum = Vec2::new(2.0, 0.0);
+        let motion = products_motion(&[1.0, 1.0], momentum, 3.0, 100.0);
+        let kinetic: f32 = motion.velocities.iter().map(|v| 0.5 * v.length_squared()).sum();
+        assert!((kinetic - 103.0).abs() < 1e-2);
+        assert!((motion.released - 100.0).abs() < 1e-2);
+        assert_eq!(motion.dissipated, 0.0);
+    }
+
+    #[test]
+    fn endothermic_event_takes_only_available_energy() {
+        let motion = products_motion(&[1.0, 1.0], Vec2::ZERO, 5.0, -20.0);
+        assert_eq!(motion.released, -5.0);
+        assert_eq!(motion.dissipated, 0.0);
+    }
+}
@@ -0,0 +1,199 @@
+#![allow(unused)]
+
+use macroquad::{prelude::*, color};
+use crate::species::*;
+
+
+/// Settings of one scalar field. Emitters and absorbers are given as species names
+/// with a rate per second.
+#[derive(Clone)]
+pub struct ScalarFieldConfig {
+    pub name: String,
+    pub diffusion: f32,
+    pub decay: f32,
+    pub color: color::Color,
+    pub emitters: Vec<(String, f32)>,
+    pub absorbers: Vec<(String, f32)>,
+}
+
+impl ScalarFieldConfig {
+    pub fn new(name: &str, diffusion: f32, decay: f32, color: color::Color) -> Self {
+        Self {
+            name: name.to_string(),
+            diffusion: diffusion,
+            decay: decay,
+            color: color,
+            emitters: vec![],
+            absorbers: vec![],
+        }
+    }
+
+    pub fn with_emitter(mut self, species: &str, rate: f32) -> Self {
+        self.emitters.push((species.to_string(), rate));
+        return self;
+    }
+
+    pub fn with_absorber(mut self, species: &str, rate: f32) -> Self {
+        self.absorbers.push((species.to_string(), rate));
+        return self;
+    }
+
+    /// Emission and absorption rates of a species.
+    pub fn rates(&self, species_id: usize, registry: &SpeciesRegistry) -> (f32, f32) {
+        let matches = |name: &String| registry.find(name) == Some(species_id);
+        let emit = self.emitters.iter().filter(|(name, _)| matches(name)).map(|(_, rate)| *rate).sum();
+        let absorb = self.absorbers.iter().filter(|(name, _)| matches(name)).map(|(_, rate)| *rate).sum();
+        return (emit, absorb);
+    }
+}
+
+pub fn default_scalar_fields() -> Vec<ScalarFieldConfig> {
+    return vec![
+        ScalarFieldConfig::new("Oxidant", 200.0, 0.05, RED)
+            .with_emitter("O", 2.0)
+            .with_emitter("O3", 4.0)
+            .with_absorber("H", 1.0),
+        ScalarFieldConfig::new("Heat", 400.0, 0.2, ORANGE),
+    ];
+}
+
+/// Concentration values on a regular grid covering the world.
+pub struct ScalarField {
+    pub cols: usize,
+    pub rows: usize,
+    pub cell_size: f32,
+    values: Vec<f32>,
+    buffer: Vec<f32>,
+}
+
+impl ScalarField {
+    pub fn new(world_size: Vec2, cell_size: f32) -> Self {
+        let cols = (world_size.x / cell_size).ceil().max(1.0) as usize;
+        let rows = (world_size.y / cell_size).ceil().max(1.0) as usize;
+        Self {
+            cols: cols,
+            rows: rows,
+            cell_size: cell_size,
+            values: vec![0.0; cols*rows],
+            buffer: vec![0.0; cols*rows],
+        }
+    }
+
+    fn cell_index(&self, pos: Vec2) -> Option<usize> {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return None;
+        }
+        let col = (pos.x / self.cell_size) as usize;
+        let row = (pos.y / self.cell_size) as usize;
+        if col >= self.cols || row >= self.rows {
+            return None;
+        }
+        return Some(row * self.cols + col);
+    }
+
+    pub fn value_at(&self, pos: Vec2) -> f32 {
+        return self.cell_index(pos).map_or(0.0, |i| self.values[i]);
+    }
+
+    pub fn add_at(&mut self, pos: Vec2, amount: f32) {
+        if let Some(i) = self.cell_index(pos) {
+            self.values[i] += amount;
+        }
+    }
+
+    /// Removes up to `amount` from the cell at position and returns what was taken.
+    pub fn take_at(&mut self, pos: Vec2, amount: f32) -> f32 {
+        let Some(i) = self.cell_index(pos) else {
+            return 0.0;
+        };
+        let taken = amount.min(self.values[i]).max(0.0);
+        self.values[i] -= taken;
+        return taken;
+    }
+
+    /// Explicit diffusion step with exponential decay. Edges are closed unless the
+    /// field wraps around like a periodic world.
+    pub fn step(&mut self, diffusion: f32, decay: f32, dt: f32, periodic: bool) {
+        let (cols, rows) = (self.cols as isize, self.rows as isize);
+        let rate = (diffusion * dt / (self.cell_size * self.cell_size)).min(0.25);
+        let fade = (-decay * dt).exp();
+        for row in 0..rows {
+            for col in 0..cols {
+                let center = self.values[(row*cols + col) as usize];
+                let mut laplacian: f32 = 0.0;
+                for (dc, dr) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                    let (mut c, mut r) = (col + dc, row + dr);
+                    if periodic {
+                        c = c.rem_euclid(cols);
+                        r = r.rem_euclid(rows);
+                    } else if c < 0 || r < 0 || c >= cols || r >= rows {
+                        continue;
+                    }
+                    laplacian += self.values[(r*cols + c) as usize] - center;
+                }
+                self.buffer[(row*cols + col) as usize] = (center + rate * laplacian) * fade;
+            }
+        }
+        std::mem::swap(&mut self.values, &mut self.buffer);
+    }
+
+    pub fn max(&self) -> f32 {
+        return self.values.iter().fold(0.0, |a, b| a.max(*b));
+    }
+
+    pub fn total(&self) -> f32 {
+        return self.values.iter().sum();
+    }
+
+    pub fn clear(&mut self) {
+        self.values.fill(0.0);
+    }
+
+    pub fn draw(&self, color: color::Color) {
+        let max = self.max();
+        if max <= 0.0 {
+            return;
+        }
+        for (i, value) in self.values.iter().enumerate() {
+            if *value <= 0.0 {
+                continue;
+            }
+            let x = (i % self.cols) as f32 * self.cell_size;
+            let y = (i / self.cols) as f32 * self.cell_size;
+            let alpha = (value / max).sqrt() * 0.6;
+            draw_rectangle(x, y, self.cell_size, self.cell_size, Color::new(color.r, color.g, color.b, alpha));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spread(periodic: bool) -> ScalarField {
+        let mut field = ScalarField::new(Vec2::new(100.0, 80.0), 10.0);
+        field.add_at(Vec2::new(5.0, 5.0), 60.0);
+        field.add_at(Vec2::new(55.0, 45.0), 40.0);
+        for _ in 0..200 {
+            field.step(50.0, 0.0, 0.1, periodic);
+        }
+        return field;
+    }
+
+    #[test]
+    fn diffusion_conserves_mass() {
+        for periodic in [false, true] {
+            let field = spread(periodic);
+            assert!((field.total() - 100.0).abs() < 1e-2, "periodic: {}, total: {}", periodic, field.total());
+            assert!(field.max() < 40.0);
+        }
+    }
+
+    #[test]
+    fn decay_removes_mass() {
+        let mut field = ScalarField::new(Vec2::new(100.0, 100.0), 10.0);
+        field.add_at(Vec2::new(50.0, 50.0), 100.0);
+        field.step(0.0, 1.0, 1.0, false);
+        assert!((field.total() - 100.0 * (-1.0f32).exp()).abs() < 1e-3);
+    }
+}
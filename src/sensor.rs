@@ -0,0 +1,123 @@
+#![allow(unused)]
+
+use std::f32::consts::PI;
+use macroquad::prelude::*;
+use rapier2d::prelude::RigidBodyHandle;
+use crate::kinetic::*;
+use crate::util::*;
+use crate::world::*;
+use crate::particle::Molecule;
+
+
+/// Object types an agent can tell apart, in the order used by the input vector.
+pub const SENSED_TYPES: [ObjectType; 3] = [ObjectType::Agent, ObjectType::Source, ObjectType::Obstacle];
+
+/// Result of one sensor ray. Angle is relative to the heading of the agent and
+/// distance is NAN when the ray hit nothing within range.
+#[derive(Clone, Copy)]
+pub struct RayHit {
+    pub angle: f32,
+    pub distance: f32,
+    pub target_type: ObjectType,
+}
+
+impl RayHit {
+    pub fn is_empty(&self) -> bool {
+        return self.distance.is_nan();
+    }
+}
+
+/// Relative angles of `rays_num` rays spread evenly over a fan centred on the heading.
+pub fn ray_angles(rays_num: usize, fan: f32) -> Vec<f32> {
+    if rays_num <= 1 {
+        return vec![0.0; rays_num];
+    }
+    let step = fan / (rays_num - 1) as f32;
+    return (0..rays_num).map(|i| -fan/2.0 + i as f32 * step).collect();
+}
+
+/// Angle of a direction relative to the heading, in [-PI, PI]. Headings follow
+/// `angle2vec2`, so the angle is measured from the y axis.
+fn relative_angle(direction: Vec2, rot: f32) -> f32 {
+    let angle = direction.x.atan2(direction.y) - rot;
+    return (angle + PI).rem_euclid(2.0*PI) - PI;
+}
+
+/// Casts a fan of rays within the field range of a molecule and stores the nearest
+/// agent, source and obstacle it sees in the detections map.
+pub fn sense(world: &World, id: u64, molecule: &Molecule, angles: &[f32], detections: &mut DetectionsMap) -> Vec<RayHit> {
+    let Some(handle) = molecule.physics_handle else {
+        return vec![];
+    };
+    let (pos, rot, range) = (molecule.pos, molecule.rot, molecule.field_range as f32);
+    let directions: Vec<Vec2> = angles.iter().map(|a| angle2vec2(rot + a)).collect();
+    let rays: Vec<RayHit> = world.cast_rays(handle, &directions, range).iter().zip(angles.iter()).map(|(hit, angle)| {
+        match hit {
+            Some((distance, target_type)) => RayHit { angle: *angle, distance: *distance, target_type: *target_type },
+            None => RayHit { angle: *angle, distance: f32::NAN, target_type: ObjectType::Empty },
+        }
+    }).collect();
+    detections.remove_detection(id);
+    for (target_id, target_type, distance, target_pos) in world.find_neighbours(handle, range) {
+        let angle = relative_angle(target_pos - pos, rot);
+        detections.add_detection(id, Detection::new_typed(distance, angle, target_pos, target_type));
+    }
+    return rays;
+}
+
+pub fn inputs_num(rays_num: usize) -> usize {
+    return rays_num * (1 + SENSED_TYPES.len()) + SENSED_TYPES.len() * 3;
+}
+
+/// Builds the network input vector with all values in [-1, 1]. Every ray gives its
+/// proximity and a one-hot type, every object type gives the proximity and the
+/// direction (sine and cosine of the relative angle) of the nearest object.
+pub fn sensor_inputs(rays: &[RayHit], detections: &DetectionsMap, id: u64, range: f32) -> Vec<f32> {
+    let mut inputs: Vec<f32> = Vec::with_capacity(inputs_num(rays.len()));
+    let proximity = |distance: f32| (1.0 - distance / range.max(1.0)).clamp(0.0, 1.0);
+    for ray in rays.iter() {
+        if ray.is_empty() {
+            inputs.extend(std::iter::repeat_n(0.0, 1 + SENSED_TYPES.len()));
+            continue;
+        }
+        inputs.push(proximity(ray.distance));
+        for object_type in SENSED_TYPES.iter() {
+            inputs.push(if ray.target_type == *object_type { 1.0 } else { 0.0 });
+        }
+    }
+    for object_type in SENSED_TYPES.iter() {
+        match detections.get_detection(id, *object_type) {
+            Some(detection) => {
+                inputs.push(proximity(detection.distance));
+                inputs.push(detection.angle.sin());
+                inputs.push(detection.angle.cos());
+            },
+            None => {
+                inputs.extend([0.0, 0.0, 0.0]);
+            },
+        }
+    }
+    return inputs;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_straight_ahead_is_at_zero() {
+        assert_eq!(relative_angle(Vec2::new(0.0, 10.0), 0.0), 0.0);
+        assert!(relative_angle(angle2vec2(2.5) * 7.0, 2.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn relative_angle_matches_ray_directions() {
+        for rot in [-3.0, -0.5, 0.0, 1.0, 3.1] {
+            for angle in ray_angles(5, PI) {
+                assert!((relative_angle(angle2vec2(rot + angle), rot) - angle).abs() < 1e-4);
+            }
+        }
+        let behind = relative_angle(Vec2::new(0.0, -1.0), 0.0);
+        assert!((behind.abs() - PI).abs() < 1e-5);
+    }
+}
@@ -2,10 +2,11 @@
 
 // main Simulation struct
 
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::f32::consts::PI;
 use macroquad::prelude::*;
 use macroquad::camera::Camera2D;
-use egui_macroquad;
+use rapier2d::prelude::RigidBodyHandle;
 use crate::particle::*;
 use crate::consts::*;
 use crate::kinetic::*;
@@ -14,6 +15,19 @@ use crate::source::*;
 use crate::util::Signals;
 use crate::world::*;
 use crate::camera::*;
+use crate::force_field::*;
+use crate::potential::*;
+use crate::thermostat::*;
+use crate::rock::*;
+use crate::species::*;
+use crate::reaction::*;
+use crate::compound::*;
+use crate::energy::*;
+use crate::scalar_field::*;
+use crate::sensor::*;
+use crate::genome::*;
+use crate::evolution::*;
+use crate::util::{random_position, random_unit_vec2, angle2vec2};
 
 
 pub struct Simulation {
@@ -25,6 +39,7 @@ pub struct Simulation {
     pub camera: Camera2D,
     pub running: bool,
     pub sim_time: f64,
+    accumulator: f32,
     config: SimConfig,
     pub ui: UISystem,
     pub sim_state: SimState,
@@ -33,7 +48,16 @@ pub struct Simulation {
     pub selected: u64,
     pub mouse_state: MouseState,
     pub molecules: MoleculesBox,
+    pub sources: SourcesBox,
+    pub death_log: VecDeque<DeathEvent>,
+    pub detections: DetectionsMap,
     pub static_elements: StaticElementBox,
+    pub species: SpeciesRegistry,
+    pub reactions: ReactionRules,
+    pub bond_graph: BondGraph,
+    compounds: Vec<Compound>,
+    pub scalar_fields: Vec<ScalarField>,
+    pub evolution: Evolution,
 }
 
 struct CamConfig {
@@ -48,6 +72,11 @@ impl Simulation {
     pub fn new(configuration: SimConfig) -> Self {
         let scr_ratio = SCREEN_WIDTH/SCREEN_HEIGHT;
         let zoom_rate = 1.0/1000.0;
+        let (species, species_error) = SpeciesRegistry::load_or_default(SPECIES_PATH);
+        let (reactions, reactions_error) = ReactionRules::load_or_default(REACTIONS_PATH, &species);
+        let mut ui = UISystem::new();
+        ui.state.data_errors = species_error.into_iter().chain(reactions_error).collect();
+        let scalar_fields = configuration.scalar_fields.iter().map(|_| ScalarField::new(Vec2::new(WORLD_W, WORLD_H), FIELD_CELL_SIZE)).collect();
         Self {
             simulation_name: String::new(),
             world_size: Vec2 { x: WORLD_W, y: WORLD_H },
@@ -57,8 +86,9 @@ impl Simulation {
             camera: create_camera(),
             running: false,
             sim_time: 0.0,    
+            accumulator: 0.0,
             config: configuration,
-            ui: UISystem::new(),
+            ui: ui,
             sim_state: SimState::new(),
             signals: Signals::new(),
             selected: 0,
@@ -66,7 +96,15 @@ impl Simulation {
             mouse_state: MouseState { pos: Vec2::NAN},
             molecules: MoleculesBox::new(),
             static_elements: StaticElementBox::new(),
-            //sources: SourcesBox::new(),
+            species: species,
+            reactions: reactions,
+            bond_graph: BondGraph::new(),
+            compounds: vec![],
+            scalar_fields: scalar_fields,
+            sources: SourcesBox::new(),
+            death_log: VecDeque::new(),
+            detections: DetectionsMap::new(),
+            evolution: Evolution::new(),
         }
     }
 
@@ -80,8 +118,18 @@ impl Simulation {
             },
         };
         self.world = World::new();
+        self.world.build(self.config.boundary);
+        self.static_elements.rebuild(&mut self.world);
         self.molecules.molecules.clear();
+        self.sources.clear();
+        self.sources.add_many(self.config.sources_num, &mut self.world);
+        self.death_log.clear();
+        self.detections.clear();
+        self.bond_graph.clear();
+        self.compounds.clear();
+        self.scalar_fields = self.config.scalar_fields.iter().map(|_| ScalarField::new(self.world_size, FIELD_CELL_SIZE)).collect();
         self.sim_time = 0.0;
+        self.accumulator = 0.0;
         self.sim_state = SimState::new();
         self.sim_state.sim_name = String::from(&self.simulation_name);
         self.signals = Signals::new();
@@ -92,12 +140,14 @@ impl Simulation {
     }
 
     pub fn init(&mut self) {
-        //self.world.build();
+        self.world.build(self.config.boundary);
         let molecules_num = self.config.molecules_init_num;
-        self.molecules.add_many_molecules(1024, &mut self.world);
-        let static_elem = StaticElement::new(Vec2::new(200.0, 200.0), 25.0, 25.0, GRAY);
-        //_ = self.static_elements.add_element(static_elem, &mut self.world);
-        //self.sources.add_many(48);
+        self.molecules.add_many_molecules(1024, &self.species, &mut self.world);
+        for _ in 0..self.config.rocks_num {
+            let rock = new_rock(random_position(WORLD_W, WORLD_H));
+            _ = self.static_elements.add_element(rock, &mut self.world);
+        }
+        self.sources.add_many(self.config.sources_num, &mut self.world);
     }
 
     pub fn autorun_new_sim(&mut self) {
@@ -109,19 +159,560 @@ impl Simulation {
         for (id, molecule) in self.molecules.get_iter_mut() {
             molecule.update2(&mut self.world);
         }
-        //for (id, fixed) in self.static_elements.get_iter_mut() {
-        //    fixed.update2(&mut self.world);
-        //}
         let dt = self.sim_state.dt;
     }
 
     pub fn update(&mut self) {
         self.signals_check();
         self.update_sim_state();
-        self.check_molecules_num();
+        if self.config.evolution.mode == EvolutionMode::Generational {
+            self.check_generation();
+        } else {
+            self.check_molecules_num();
+            self.evolution.started = self.sim_state.sim_time;
+        }
+        self.check_sources_num();
         self.calc_selection_time();
+        self.accumulator += self.sim_state.dt * self.config.time_scale;
+        let mut steps = 0;
+        while self.accumulator >= FIX_DT && steps < MAX_SUBSTEPS {
+            self.step_world();
+            self.accumulator -= FIX_DT;
+            steps += 1;
+        }
+        if steps >= MAX_SUBSTEPS {
+            self.accumulator = self.accumulator.min(FIX_DT);
+        }
+        self.update_molecules();
+        self.update_compounds();
+        if self.config.sensors {
+            self.update_sensors();
+        }
+        if self.config.actuators {
+            let max_speed = self.config.molecule_speed;
+            for (_, molecule) in self.molecules.get_iter_mut() {
+                molecule.think(max_speed);
+            }
+        }
+    }
+
+    /// Turns actions of molecules into forces on their bodies and charges the energy
+    /// cost. Thrust is limited so a molecule cannot push itself faster than
+    /// `molecule_speed`, turning is limited to `molecule_turn`.
+    fn apply_actuators(&mut self) {
+        let speed = self.config.molecule_speed;
+        let mut work: f32 = 0.0;
+        let mut emissions: Vec<Vec2> = vec![];
+        for (_, molecule) in self.molecules.get_iter_mut() {
+            let Some(handle) = molecule.physics_handle else {
+                continue;
+            };
+            let data = self.world.get_physics_data(handle);
+            let actions = molecule.actions;
+            let heading = angle2vec2(molecule.rot);
+            let limit = (1.0 - data.velocity.dot(heading) / speed.max(1.0)).clamp(0.0, 1.0);
+            let force = heading * actions.thrust * THRUST_RATE * speed * data.mass * limit;
+            let target_spin = actions.torque * self.config.molecule_turn;
+            let torque = (target_spin - data.angular_velocity) * data.inertia * TURN_RATE;
+            self.world.add_force_and_torque(handle, force, torque);
+            work += force.dot(data.velocity) * FIX_DT;
+            if actions.brake > 0.0 {
+                let velocity = data.velocity * (1.0 - actions.brake * BRAKE_RATE * FIX_DT).max(0.0);
+                self.world.set_velocity(handle, velocity);
+                work += 0.5 * data.mass * (velocity.length_squared() - data.velocity.length_squared());
+            }
+            if actions.emit {
+                emissions.push(data.position);
+            }
+            molecule.eng = (molecule.eng - actions.effort() * ACTION_COST * molecule.size * FIX_DT).max(0.0);
+        }
+        self.sim_state.energy.add_external(work);
+        if let Some(field) = self.scalar_fields.first_mut() {
+            for pos in emissions {
+                field.add_at(pos, EMIT_RATE * FIX_DT);
+            }
+        }
+    }
+
+    fn update_sensors(&mut self) {
+        let angles = ray_angles(SENSOR_RAYS, SENSOR_FAN);
+        self.detections.clear();
+        for (id, molecule) in self.molecules.get_iter_mut() {
+            molecule.rays = sense(&self.world, *id, molecule, &angles, &mut self.detections);
+            molecule.inputs = sensor_inputs(&molecule.rays, &self.detections, *id, molecule.field_range as f32);
+        }
+    }
+
+    fn step_world(&mut self) {
         self.update_molecules();
+        let (kinetic, potential) = self.measure_energy();
+        self.sim_state.energy.begin_step(kinetic, potential);
+        self.world.reset_forces();
+        if self.config.actuators {
+            self.apply_actuators();
+        }
+        let field_work = self.world.apply_force_fields(&self.config.force_fields);
+        self.sim_state.energy.add_external(field_work);
+        self.world.apply_interactions(&self.config.potential);
+        self.world.apply_electrostatics(&self.get_charges(), self.config.charge_k);
         self.world.step_physics();
+        self.sim_state.energy.add_external(-self.world.get_contact_losses());
+        self.process_bonds();
+        // Reactions and decays would replace evolved genomes by random products.
+        if self.config.evolution.mode == EvolutionMode::Continuous {
+            if self.config.reactions {
+                self.process_reactions();
+            }
+            self.process_decays();
+        }
+        self.drain_sources();
+        if self.config.metabolism {
+            self.process_metabolism();
+        }
+        if self.config.reproduction && self.config.evolution.mode == EvolutionMode::Continuous {
+            self.process_fission();
+        }
+        self.update_scalar_fields();
+        let kinetic = self.world.get_kinetic_energy();
+        self.config.thermostat.apply(&mut self.world, self.config.target_temperature, FIX_DT);
+        self.sim_state.energy.add_external(self.world.get_kinetic_energy() - kinetic);
+        if self.config.boundary == BoundaryMode::Open {
+            self.despawn_escaped();
+        }
+        self.world.update_query_pipeline();
+        let (kinetic, potential) = self.measure_energy();
+        self.sim_state.energy.end_step(kinetic, potential, self.config.energy_tolerance);
+        self.sim_state.sim_time += FIX_DT as f64;
+        self.sample_population();
+    }
+
+    fn despawn_escaped(&mut self) {
+        let margin = 50.0;
+        let mut escaped: Vec<u64> = vec![];
+        for (id, molecule) in self.molecules.get_iter() {
+            let pos = molecule.pos;
+            if pos.x < -margin || pos.y < -margin || pos.x > self.world_size.x+margin || pos.y > self.world_size.y+margin {
+                escaped.push(*id);
+            }
+        }
+        for id in escaped {
+            _ = self.discard_molecule(id);
+        }
+    }
+
+    /// Molecules touching a source drain its energy, depleted sources are removed.
+    fn drain_sources(&mut self) {
+        let mut drained: Vec<(u64, f32)> = vec![];
+        for (id, _) in self.molecules.get_iter() {
+            let Some(touches) = self.world.get_touches(*id) else {
+                continue;
+            };
+            for hit in touches.iter() {
+                if hit.target_type != ObjectType::Source {
+                    continue;
+                }
+                if let Some(source) = self.sources.get_mut(hit.target_id) {
+                    let eng = source.drain_eng(SOURCE_DRAIN_RATE * FIX_DT);
+                    drained.push((*id, eng));
+                }
+            }
+        }
+        for (id, eng) in drained {
+            if let Some(molecule) = self.molecules.get_mut(id) {
+                molecule.add_eng(eng);
+            }
+        }
+        let mut depleted: Vec<u64> = vec![];
+        for (id, source) in self.sources.get_iter_mut() {
+            source.update(FIX_DT);
+            if !source.alife {
+                depleted.push(*id);
+            }
+        }
+        for id in depleted {
+            self.sources.remove(id, &mut self.world);
+        }
+    }
+
+    fn update_scalar_fields(&mut self) {
+        let periodic = self.config.boundary == BoundaryMode::Periodic;
+        for (field, config) in self.scalar_fields.iter_mut().zip(self.config.scalar_fields.iter()) {
+            let rates: Vec<(f32, f32)> = (0..self.species.count()).map(|id| config.rates(id, &self.species)).collect();
+            for (_, molecule) in self.molecules.get_iter_mut() {
+                let (emit, absorb) = rates.get(molecule.species).copied().unwrap_or((0.0, 0.0));
+                if emit > 0.0 {
+                    field.add_at(molecule.pos, emit * FIX_DT);
+                }
+                if absorb > 0.0 {
+                    let absorbed = field.take_at(molecule.pos, absorb * FIX_DT);
+                    molecule.add_eng(absorbed * ABSORB_ENG_RATIO);
+                }
+            }
+            field.step(config.diffusion, config.decay, FIX_DT, periodic);
+        }
+        self.sim_state.field_totals = self.scalar_fields.iter().map(|f| f.total()).collect();
+    }
+
+    /// Deposits the heat of an exothermic event into the "Heat" field, an endothermic
+    /// event draws heat from it instead.
+    fn add_heat(&mut self, pos: Vec2, energy: f32) {
+        if let Some(i) = self.config.scalar_fields.iter().position(|f| f.name == "Heat") {
+            if let Some(field) = self.scalar_fields.get_mut(i) {
+                if energy >= 0.0 {
+                    field.add_at(pos, energy * HEAT_RATIO);
+                } else {
+                    _ = field.take_at(pos, -energy * HEAT_RATIO);
+                }
+            }
+        }
+    }
+
+    /// Potential energy between a molecule and the rest of the world.
+    fn get_molecule_potential(&mut self, handle: RigidBodyHandle) -> f32 {
+        self.world.update_query_pipeline();
+        return self.world.get_body_potential_energy(handle, &self.config.potential, &self.get_charges(), self.config.charge_k);
+    }
+
+    fn measure_energy(&self) -> (f32, f32) {
+        let kinetic = self.world.get_kinetic_energy();
+        let potential = self.world.get_potential_energy(&self.config.potential, &self.get_charges(), self.config.charge_k);
+        return (kinetic, potential);
+    }
+
+    /// In generational mode every removed molecule is scored before it is gone. The
+    /// potential energy of its pairs and bonds leaves the ledger with it.
+    fn remove_molecule(&mut self, id: u64) -> Option<Molecule> {
+        if let Some(handle) = self.molecules.get(id).and_then(|m| m.physics_handle) {
+            let energy = self.get_molecule_potential(handle);
+            self.sim_state.energy.add_external(-energy);
+        }
+        self.bond_graph.remove_molecule(id);
+        let molecule = self.molecules.remove(id, &mut self.world)?;
+        if self.config.evolution.mode == EvolutionMode::Generational {
+            self.evolution.record(&molecule);
+        }
+        return Some(molecule);
+    }
+
+    /// Removes a molecule from the simulation and books its kinetic energy as lost.
+    fn discard_molecule(&mut self, id: u64) -> Option<Molecule> {
+        if let Some(handle) = self.molecules.get(id).and_then(|m| m.physics_handle) {
+            let data = self.world.get_physics_data(handle);
+            self.sim_state.energy.add_external(-0.5 * data.mass * data.velocity.length_squared());
+        }
+        return self.remove_molecule(id);
+    }
+
+    fn process_metabolism(&mut self) {
+        let mut dead: Vec<u64> = vec![];
+        for (id, molecule) in self.molecules.get_iter_mut() {
+            molecule.metabolize(FIX_DT);
+            if !molecule.alife {
+                dead.push(*id);
+            }
+        }
+        for id in dead {
+            if let Some(molecule) = self.discard_molecule(id) {
+                let death = DeathEvent { id: id, species: molecule.species, position: molecule.pos, lifetime: molecule.lifetime, time: self.sim_state.sim_time };
+                self.death_log.push_back(death);
+                if self.death_log.len() > DEATH_LOG_SIZE {
+                    self.death_log.pop_front();
+                }
+                self.sim_state.deaths_num += 1;
+            }
+        }
+    }
+
+    /// Forms bonds between molecules whose fields started to overlap if both still
+    /// have free valence, and drops bonds broken by the physics world from the graph.
+    fn process_bonds(&mut self) {
+        for (id1, id2) in self.world.get_broken_bonds().to_vec() {
+            self.bond_graph.remove_bond(id1, id2);
+        }
+        self.sim_state.energy.release_chemical(-self.world.get_broken_bonds_energy());
+        for (id1, id2) in self.world.get_new_field_contacts().to_vec() {
+            if self.bond_graph.are_bonded(id1, id2) {
+                continue;
+            }
+            let (Some(molecule1), Some(molecule2)) = (self.molecules.get(id1), self.molecules.get(id2)) else {
+                continue;
+            };
+            let (Some(handle1), Some(handle2)) = (molecule1.physics_handle, molecule2.physics_handle) else {
+                continue;
+            };
+            if !self.has_free_valence(id1) || !self.has_free_valence(id2) || !self.wants_bond(id1) || !self.wants_bond(id2) {
+                continue;
+            }
+            if let Some(joint_handle) = self.world.create_bond(handle1, handle2) {
+                self.bond_graph.add_bond(id1, id2, self.sim_state.sim_time);
+                self.sim_state.energy.release_chemical(self.world.get_bond_energy(joint_handle));
+            }
+        }
+    }
+
+    /// Bonding is up to the network of the molecule only when molecules are evolving
+    /// agents; in the plain chemistry sandbox every contact with free valence bonds.
+    fn wants_bond(&self, id: u64) -> bool {
+        return !self.agents_evolve() || self.molecules.get(id).is_some_and(|m| m.actions.bond);
+    }
+
+    /// Molecules act on their own and their networks are subject to selection.
+    fn agents_evolve(&self) -> bool {
+        return self.config.actuators && (self.config.reproduction || self.config.evolution.mode == EvolutionMode::Generational);
+    }
+
+    fn has_free_valence(&self, id: u64) -> bool {
+        let Some(molecule) = self.molecules.get(id) else {
+            return false;
+        };
+        return self.bond_graph.degree(id) < molecule.genome.valence as usize;
+    }
+
+    fn update_compounds(&mut self) {
+        self.compounds.clear();
+        for members in self.bond_graph.components() {
+            let compound = Compound::new(members, &self.bond_graph, &self.molecules, &self.species, &self.world, self.sim_state.sim_time);
+            self.compounds.push(compound);
+        }
+        self.sim_state.compounds_num = self.compounds.len() as i32;
+    }
+
+    pub fn get_compounds(&self) -> &[Compound] {
+        return &self.compounds;
+    }
+
+    /// Compound the molecule belongs to, None for a free molecule.
+    pub fn get_compound(&self, id: u64) -> Option<&Compound> {
+        return self.compounds.iter().find(|c| c.contains(id));
+    }
+
+    fn process_reactions(&mut self) {
+        let mut candidates: Vec<(u64, u64)> = vec![];
+        for (id, _) in self.molecules.get_iter() {
+            if let Some(collisions) = self.world.get_collisions(*id) {
+                for hit in collisions.iter() {
+                    if hit.target_type == ObjectType::Agent && *id < hit.target_id {
+                        candidates.push((*id, hit.target_id));
+                    }
+                }
+            }
+        }
+        let mut reacted: HashSet<u64> = HashSet::new();
+        for (id1, id2) in candidates {
+            if reacted.contains(&id1) || reacted.contains(&id2) {
+                continue;
+            }
+            let (Some(molecule1), Some(molecule2)) = (self.molecules.get(id1), self.molecules.get(id2)) else {
+                continue;
+            };
+            let (Some(handle1), Some(handle2)) = (molecule1.physics_handle, molecule2.physics_handle) else {
+                continue;
+            };
+            let Some(rule) = self.reactions.find(molecule1.species, molecule2.species).cloned() else {
+                continue;
+            };
+            let approach = self.world.get_pre_step_velocity(handle1) - self.world.get_pre_step_velocity(handle2);
+            if approach.length() < rule.activation_speed {
+                continue;
+            }
+            if rand::gen_range(0.0, 1.0) > rule.probability {
+                continue;
+            }
+            self.react(id1, id2, &rule);
+            reacted.insert(id1);
+            reacted.insert(id2);
+        }
+    }
+
+    /// Replaces two reactants with the products of a rule. Momentum is conserved and
+    /// the released (or absorbed) energy changes the products' relative motion.
+    fn react(&mut self, id1: u64, id2: u64, rule: &ReactionRule) {
+        let mut mass: f32 = 0.0;
+        let mut momentum = Vec2::ZERO;
+        let mut center = Vec2::ZERO;
+        let mut kinetic_energy: f32 = 0.0;
+        let mut charge: f32 = 0.0;
+        let mut eng: f32 = 0.0;
+        for id in [id1, id2] {
+            let Some(molecule) = self.molecules.get(id) else {
+                return;
+            };
+            let Some(handle) = molecule.physics_handle else {
+                return;
+            };
+            let data = self.world.get_physics_data(handle);
+            mass += data.mass;
+            momentum += data.velocity * data.mass;
+            center += data.position * data.mass;
+            kinetic_energy += 0.5 * data.mass * data.velocity.length_squared();
+            charge += molecule.charge;
+            eng += molecule.eng;
+        }
+        center /= mass;
+        self.remove_molecule(id1);
+        self.remove_molecule(id2);
+        let (motion, products) = self.spawn_products(&rule.products, center, momentum, charge, kinetic_energy, rule.energy);
+        self.share_eng(&products, eng + motion.released.max(0.0) * REACTION_ENG_RATIO);
+        self.book_products_energy(center, &motion);
+        self.sim_state.reactions_num += 1;
+    }
+
+    /// Places products around a centre so that they carry given total momentum and the
+    /// kinetic energy of the reactants plus `energy` (as far as momentum allows). The
+    /// first product keeps the whole charge. Returns the energy balance of the event
+    /// and ids of the products.
+    fn spawn_products(&mut self, species_ids: &[usize], center: Vec2, momentum: Vec2, charge: f32, kinetic_energy: f32, energy: f32) -> (ProductsMotion, Vec<u64>) {
+        let n = species_ids.len();
+        let spin = rand::gen_range(0.0, 2.0*PI);
+        let mut products: Vec<RigidBodyHandle> = vec![];
+        let mut ids: Vec<u64> = vec![];
+        for (i, species_id) in species_ids.iter().enumerate() {
+            let mut molecule = Molecule::from_genome(&Genome::random(*species_id, &self.species), &self.species);
+            molecule.pos = center;
+            if n > 1 {
+                molecule.pos += angle2vec2(spin + 2.0*PI*i as f32 / n as f32) * molecule.size * 1.5;
+            }
+            molecule.set_charge(if i == 0 { charge } else { 0.0 });
+            let id = self.molecules.add_molecule(molecule, &self.species, &mut self.world);
+            if let Some(handle) = self.molecules.get(id).and_then(|m| m.physics_handle) {
+                let energy = self.get_molecule_potential(handle);
+                self.sim_state.energy.add_external(energy);
+                products.push(handle);
+                ids.push(id);
+            }
+        }
+        let masses: Vec<f32> = products.iter().map(|h| self.world.get_physics_data(*h).mass).collect();
+        let motion = products_motion(&masses, momentum, kinetic_energy, energy);
+        for (handle, velocity) in products.iter().zip(motion.velocities.iter()) {
+            self.world.set_velocity(*handle, *velocity);
+        }
+        return (motion, ids);
+    }
+
+    /// Released chemical energy goes into the products' motion, the dissipated part is
+    /// deposited as heat and leaves the ledger as external work.
+    fn book_products_energy(&mut self, pos: Vec2, motion: &ProductsMotion) {
+        self.sim_state.energy.release_chemical(motion.released);
+        self.sim_state.energy.add_external(-motion.dissipated);
+        let heat = if motion.dissipated > 0.0 { motion.released.max(motion.dissipated) } else { motion.released };
+        self.add_heat(pos, heat);
+    }
+
+    fn process_fission(&mut self) {
+        let ready: Vec<u64> = self.molecules.get_iter().filter(|(_, m)| m.is_ready_to_split()).map(|(id, _)| *id).collect();
+        for id in ready {
+            self.split(id);
+        }
+    }
+
+    /// Replaces a molecule by two mutated daughters pushed apart along a random axis.
+    /// Momentum and kinetic energy of the parent are kept.
+    fn split(&mut self, id: u64) {
+        let Some(parent) = self.molecules.get(id) else {
+            return;
+        };
+        let Some(handle) = parent.physics_handle else {
+            return;
+        };
+        let data = self.world.get_physics_data(handle);
+        let mut daughters = [parent.daughter(&self.species, self.config.mutation_rate, self.config.mutation_magnitude), parent.daughter(&self.species, self.config.mutation_rate, self.config.mutation_magnitude)];
+        daughters[0].set_charge(parent.charge);
+        let axis = random_unit_vec2();
+        let momentum = data.velocity * data.mass;
+        let kinetic_energy = 0.5 * data.mass * data.velocity.length_squared();
+        self.remove_molecule(id);
+        let mut handles: Vec<RigidBodyHandle> = vec![];
+        for (i, mut daughter) in daughters.into_iter().enumerate() {
+            let side = if i == 0 { 1.0 } else { -1.0 };
+            daughter.pos = data.position + axis * side * daughter.size * 1.2;
+            let daughter_id = self.molecules.add_molecule(daughter, &self.species, &mut self.world);
+            if let Some(handle) = self.molecules.get(daughter_id).and_then(|m| m.physics_handle) {
+                let energy = self.get_molecule_potential(handle);
+                self.sim_state.energy.add_external(energy);
+                handles.push(handle);
+            }
+        }
+        let masses: Vec<f32> = handles.iter().map(|h| self.world.get_physics_data(*h).mass).collect();
+        let total_mass: f32 = masses.iter().sum();
+        if total_mass <= 0.0 {
+            return;
+        }
+        let internal_energy = (kinetic_energy - 0.5 * momentum.length_squared() / total_mass).max(0.0);
+        for (handle, velocity) in handles.iter().zip(split_momentum(&masses, momentum, internal_energy)) {
+            self.world.set_velocity(*handle, velocity);
+        }
+        self.sim_state.births_num += 2;
+    }
+
+    /// Splits metabolic energy equally among molecules.
+    fn share_eng(&mut self, ids: &[u64], eng: f32) {
+        if ids.is_empty() {
+            return;
+        }
+        let share = eng / ids.len() as f32;
+        for id in ids.iter() {
+            if let Some(molecule) = self.molecules.get_mut(*id) {
+                molecule.eng = share.min(molecule.max_eng);
+            }
+        }
+    }
+
+    fn process_decays(&mut self) {
+        let mut decayed: Vec<u64> = vec![];
+        for (id, molecule) in self.molecules.get_iter_mut() {
+            if let Some(timer) = molecule.decay_timer.as_mut() {
+                if timer.update(FIX_DT) {
+                    decayed.push(*id);
+                }
+            }
+        }
+        for id in decayed {
+            self.decay(id);
+        }
+    }
+
+    fn decay(&mut self, id: u64) {
+        let Some(molecule) = self.molecules.get(id) else {
+            return;
+        };
+        let (Some(handle), Some(species)) = (molecule.physics_handle, self.species.get(molecule.species)) else {
+            return;
+        };
+        let products = species.decay_products.clone();
+        let energy = species.decay_energy;
+        let charge = molecule.charge;
+        let eng = molecule.eng;
+        let data = self.world.get_physics_data(handle);
+        self.remove_molecule(id);
+        let kinetic_energy = 0.5 * data.mass * data.velocity.length_squared();
+        let (motion, product_ids) = self.spawn_products(&products, data.position, data.velocity * data.mass, charge, kinetic_energy, energy);
+        self.share_eng(&product_ids, eng);
+        self.book_products_energy(data.position, &motion);
+        self.sim_state.decays_num += 1;
+    }
+
+    fn sample_population(&mut self) {
+        let time = self.sim_state.sim_time;
+        if self.sim_state.population.last_time().is_some_and(|t| time - t < POPULATION_INTERVAL) {
+            return;
+        }
+        let mut counts: Vec<usize> = vec![0; self.species.count()];
+        for (_, molecule) in self.molecules.get_iter() {
+            if let Some(count) = counts.get_mut(molecule.species) {
+                *count += 1;
+            }
+        }
+        self.sim_state.population.add_sample(time, &counts);
+    }
+
+    fn get_charges(&self) -> HashMap<RigidBodyHandle, f32> {
+        let mut charges: HashMap<RigidBodyHandle, f32> = HashMap::new();
+        for (_, molecule) in self.molecules.get_iter() {
+            if let Some(handle) = molecule.physics_handle {
+                charges.insert(handle, molecule.charge);
+            }
+        }
+        return charges;
     }
 
     pub fn draw(&self) {
@@ -129,7 +720,10 @@ impl Simulation {
         set_camera(&self.camera);
         clear_background(BLACK);
         draw_rectangle_lines(0.0, 0.0, self.world_size.x, self.world_size.y, 3.0, WHITE);
-        self.draw_grid(50);
+        self.draw_scalar_field();
+        self.draw_grid(FIELD_CELL_SIZE as u32);
+        self.draw_bonds();
+        self.draw_sources();
         self.draw_molecules();
         self.draw_statics();
     }
@@ -138,14 +732,58 @@ impl Simulation {
         for (id, molecule) in self.molecules.get_iter() {
             molecule.draw();
         }
-        match self.molecules.get(self.selected) {
-            Some(selected_molecule) => {
-                let pos = Vec2::new(selected_molecule.pos.x, selected_molecule.pos.y);
-                let s = selected_molecule.size;
-                draw_circle_lines(pos.x, pos.y, 2.0*s+(self.select_phase.sin()*s*0.5), 1.0, ORANGE);
-            },
-            None => {},
-        };
+        if let Some(compound) = self.get_compound(self.selected) {
+            self.draw_compound(compound);
+        }
+        if let Some(molecule) = self.molecules.get(self.selected) {
+            self.draw_sensors(molecule);
+        }
+        if let Some(selected_molecule) = self.molecules.get(self.selected) {
+            let pos = Vec2::new(selected_molecule.pos.x, selected_molecule.pos.y);
+            let s = selected_molecule.size;
+            draw_circle_lines(pos.x, pos.y, 2.0*s+(self.select_phase.sin()*s*0.5), 1.0, ORANGE);
+        }
+    }
+
+    fn draw_sensors(&self, molecule: &Molecule) {
+        for ray in molecule.rays.iter() {
+            let dir = angle2vec2(molecule.rot + ray.angle);
+            let (length, color) = match ray.target_type {
+                ObjectType::Agent => (ray.distance, SKYBLUE),
+                ObjectType::Source => (ray.distance, YELLOW),
+                ObjectType::Obstacle => (ray.distance, RED),
+                ObjectType::Empty => (molecule.field_range as f32, Color::new(0.5, 0.5, 0.5, 0.3)),
+            };
+            let end = molecule.pos + dir * length;
+            draw_line(molecule.pos.x, molecule.pos.y, end.x, end.y, 0.5, color);
+        }
+    }
+
+    fn draw_compound(&self, compound: &Compound) {
+        for (id1, id2) in compound.bonds.iter() {
+            if let (Some(m1), Some(m2)) = (self.molecules.get(*id1), self.molecules.get(*id2)) {
+                if m1.pos.distance(m2.pos) < self.world_size.x/2.0 {
+                    draw_line(m1.pos.x, m1.pos.y, m2.pos.x, m2.pos.y, 2.0, YELLOW);
+                }
+            }
+        }
+        for id in compound.members.iter() {
+            if let Some(molecule) = self.molecules.get(*id) {
+                draw_circle_lines(molecule.pos.x, molecule.pos.y, molecule.size+2.0, 1.0, YELLOW);
+            }
+        }
+    }
+
+    fn draw_bonds(&self) {
+        for (p1, p2) in self.world.get_bond_lines() {
+            draw_line(p1.x, p1.y, p2.x, p2.y, 1.0, SKYBLUE);
+        }
+    }
+
+    fn draw_sources(&self) {
+        for (_, source) in self.sources.get_iter() {
+            source.draw();
+        }
     }
 
     fn draw_statics(&self) {
@@ -154,6 +792,14 @@ impl Simulation {
         }
     }
 
+    fn draw_scalar_field(&self) {
+        if let Some(i) = self.config.field_overlay {
+            if let (Some(field), Some(config)) = (self.scalar_fields.get(i), self.config.scalar_fields.get(i)) {
+                field.draw(config.color);
+            }
+        }
+    }
+
     fn draw_grid(&self, cell_size: u32) {
         let w = self.world_size.x;
         let h = self.world_size.y;
@@ -169,16 +815,44 @@ impl Simulation {
 
     pub fn signals_check(&mut self) {
         if self.signals.spawn_molecule {
-            let molecule = Molecule::new();
-            self.molecules.add_molecule(molecule, &mut self.world);
+            let species_id = self.signals.spawn_species.min(self.species.count()-1);
+            let mut molecule = Molecule::from_genome(&Genome::random(species_id, &self.species), &self.species);
+            molecule.set_charge(self.signals.spawn_charge);
+            self.molecules.add_molecule(molecule, &self.species, &mut self.world);
             self.signals.spawn_molecule = false;
         }
+        if let Some(id) = self.signals.select_molecule.take() {
+            if self.molecules.get(id).is_some() {
+                self.selected = id;
+            }
+        }
+        if self.signals.clear_obstacles {
+            self.static_elements.clear(&mut self.world);
+            self.signals.clear_obstacles = false;
+        }
         if self.signals.new_sim {
             self.signals.new_sim = false;
             //if !self.signals.new_sim_name.is_empty() {
             self.reset_sim(Some(&self.signals.new_sim_name.to_owned()));
+            self.evolution.clear();
             //}
         }
+        if self.signals.pause {
+            self.signals.pause = false;
+            self.running = !self.running;
+            self.accumulator = 0.0;
+        }
+        if self.signals.step_frames > 0 {
+            if !self.running {
+                for _ in 0..self.signals.step_frames {
+                    self.step_world();
+                }
+                self.update_molecules();
+                self.update_compounds();
+            }
+            self.signals.step_frames = 0;
+        }
+        self.sim_state.running = self.running;
     }
 
     fn get_selected(&self) -> Option<&Molecule> {
@@ -198,54 +872,127 @@ impl Simulation {
     }
 
     fn mouse_input(&mut self) {
-        if is_mouse_button_released(MouseButton::Left) {
-            if !self.ui.pointer_over {
-                self.selected = 0;
-                let (mouse_posx, mouse_posy) = mouse_position();
-                let offset = self.camera.offset;
-                let target = self.camera.target;
-                let zoom = self.camera.zoom;
-                let rotation = self.camera.rotation;
-                let mouse_pos = Vec2::new(mouse_posx, mouse_posy);
-                //println!("rel mouse: [{} | {}]", rel_x, rel_y);
-                //println!("offset: [{} | {}], zoom: [{} | {}], target: [{} | {}], rotation: [{}]", offset.x, offset.y, zoom.x, zoom.y, target.x, target.y, rotation);
-                let rel_coords = self.camera.screen_to_world(mouse_pos);
-                //println!("SCR COORDS: [{} | {}] ==> WORLD COORDS: [{} | {}]", mouse_posx, mouse_posy, rel_coords.x, rel_coords.y);
-                for (id, molecule) in self.molecules.get_iter() {
-                    if contact_mouse(rel_coords, molecule.pos, molecule.size) {
-                        self.selected = *id;
-                        break; 
-                    }
+        let placing = self.signals.spawn_box || self.signals.spawn_circle || self.signals.spawn_rock;
+        if placing && is_mouse_button_released(MouseButton::Left) && !self.ui.pointer_over {
+            let world_pos = self.camera.screen_to_world(Vec2::from(mouse_position()));
+            self.place_obstacle(world_pos);
+        } else if is_mouse_button_released(MouseButton::Left) && !self.ui.pointer_over {
+            self.selected = 0;
+            let (mouse_posx, mouse_posy) = mouse_position();
+            let offset = self.camera.offset;
+            let target = self.camera.target;
+            let zoom = self.camera.zoom;
+            let rotation = self.camera.rotation;
+            let mouse_pos = Vec2::new(mouse_posx, mouse_posy);
+            //println!("rel mouse: [{} | {}]", rel_x, rel_y);
+            //println!("offset: [{} | {}], zoom: [{} | {}], target: [{} | {}], rotation: [{}]", offset.x, offset.y, zoom.x, zoom.y, target.x, target.y, rotation);
+            let rel_coords = self.camera.screen_to_world(mouse_pos);
+            //println!("SCR COORDS: [{} | {}] ==> WORLD COORDS: [{} | {}]", mouse_posx, mouse_posy, rel_coords.x, rel_coords.y);
+            for (id, molecule) in self.molecules.get_iter() {
+                if contact_mouse(rel_coords, molecule.pos, molecule.size) {
+                    self.selected = *id;
+                    break; 
                 }
             }
         }
     }
 
+    /// Places the obstacle armed in the Creator at a world position. Spots overlapping
+    /// other bodies are rejected and the obstacle stays armed for another click.
+    fn place_obstacle(&mut self, pos: Vec2) {
+        let element = if self.signals.spawn_box {
+            let size = rand::gen_range(16.0, 64.0);
+            StaticElement::new(pos, size, size*rand::gen_range(0.5, 2.0), GRAY)
+        } else if self.signals.spawn_circle {
+            StaticElement::new_circle(pos, rand::gen_range(8.0, 32.0), GRAY)
+        } else {
+            new_rock(pos)
+        };
+        if !self.world.is_area_free(&pos, &element.shape) {
+            return;
+        }
+        _ = self.static_elements.add_element(element, &mut self.world);
+        self.signals.spawn_box = false;
+        self.signals.spawn_circle = false;
+        self.signals.spawn_rock = false;
+    }
+
     fn update_sim_state(&mut self) {
         self.sim_state.fps = get_fps();
         self.sim_state.dt = get_frame_time();
-        self.sim_state.sim_time += self.sim_state.dt as f64;
         let (mouse_x, mouse_y) = mouse_position();
         self.mouse_state.pos = Vec2::new(mouse_x, mouse_y);
         self.sim_state.molecules_num = self.molecules.count() as i32;
+        self.sim_state.sources_num = self.sources.count() as i32;
+        self.sim_state.generation_time = self.evolution.elapsed(self.sim_state.sim_time);
         self.sim_state.physics_num = self.world.get_physics_obj_num() as i32;
+        self.sim_state.bonds_num = self.world.get_bonds_num() as i32;
+        self.sim_state.temperature = self.world.get_kinetic_temperature();
+        let impacts = self.world.get_impact_stats();
+        self.sim_state.wall_pressure = impacts.wall_pressure;
+        self.sim_state.collision_rate = impacts.collision_rate;
+        self.sim_state.impact_energy_rate = impacts.impact_energy_rate;
+        self.sim_state.impact_max = impacts.max_impact();
+        self.sim_state.impact_mean = impacts.mean_impact();
+        self.sim_state.selected_impact = impacts.get_impact(self.selected);
+    }
+
+    fn check_sources_num(&mut self) {
+        if self.sources.count() < self.config.sources_min_num {
+            self.sources.add_source(Source::new(), &mut self.world);
+        }
     }
 
     fn check_molecules_num(&mut self) {
         if self.sim_state.molecules_num < (self.config.molecule_min_num as i32) {
-            let molecule = Molecule::new();
-            self.molecules.add_molecule(molecule, &mut self.world);
+            let molecule = Molecule::from_genome(&Genome::random(self.species.random_id(), &self.species), &self.species);
+            self.molecules.add_molecule(molecule, &self.species, &mut self.world);
+        }
+    }
+
+    /// Ends the generation when its time is up or nobody is left alive.
+    fn check_generation(&mut self) {
+        let elapsed = self.evolution.elapsed(self.sim_state.sim_time);
+        if elapsed >= self.config.evolution.generation_time as f64 || self.molecules.count() == 0 {
+            self.next_generation();
+        }
+    }
+
+    /// Scores the survivors, breeds the next generation, resets the world and
+    /// repopulates it. An extinct population is replaced by random genomes.
+    fn next_generation(&mut self) {
+        for (_, molecule) in self.molecules.get_iter() {
+            self.evolution.record(molecule);
+        }
+        let mut genomes = self.evolution.breed(&self.config.evolution, self.config.mutation_rate, self.config.mutation_magnitude);
+        if genomes.is_empty() {
+            genomes = (0..self.config.evolution.generation_size).map(|_| Genome::random(self.species.random_id(), &self.species)).collect();
         }
+        let sim_name = self.simulation_name.clone();
+        let running = self.running;
+        let signals = std::mem::replace(&mut self.signals, Signals::new());
+        self.reset_sim(Some(&sim_name));
+        self.running = running;
+        self.signals = signals;
+        self.sim_state.running = running;
+        for genome in genomes.iter() {
+            let molecule = Molecule::from_genome(genome, &self.species);
+            self.molecules.add_molecule(molecule, &self.species, &mut self.world);
+        }
+        self.evolution.started = self.sim_state.sim_time;
+        self.sim_state.generation = self.evolution.generation;
+        self.sim_state.fitness = self.evolution.history.clone();
     }
 
     fn calc_selection_time(&mut self) {
         self.select_phase += self.sim_state.dt*4.0;
-        self.select_phase = self.select_phase%(2.0*PI as f32);
+        self.select_phase %= 2.0*PI;
     }
 
     pub fn process_ui(&mut self) {
-        let marked_molecule = self.molecules.get(self.selected);
-        self.ui.ui_process(&self.sim_state, marked_molecule, &mut self.signals)
+        let marked_molecule = self.molecules.get(self.selected).map(|m| (self.selected, m));
+        let compound = self.compounds.iter().find(|c| c.contains(self.selected));
+        self.ui.ui_process(&self.sim_state, marked_molecule, compound, &mut self.signals, &mut self.config, &self.species)
     }
 
     pub fn draw_ui(&self) {
@@ -259,11 +1006,35 @@ impl Simulation {
 
 
 //?         [[[SIM_CONFIG]]]
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct SimConfig {
     pub molecules_init_num: usize,
     pub molecule_min_num: usize,
     pub molecule_speed: f32,
+    pub molecule_turn: f32,
+    pub force_fields: Vec<ForceField>,
+    pub potential: Potential,
+    pub charge_k: f32,
+    pub boundary: BoundaryMode,
+    pub time_scale: f32,
+    pub thermostat: Thermostat,
+    pub target_temperature: f32,
+    pub energy_tolerance: f32,
+    pub rocks_num: usize,
+    pub sources_num: usize,
+    pub sources_min_num: usize,
+    pub reactions: bool,
+    pub metabolism: bool,
+    pub reproduction: bool,
+    /// Probability that a gene changes when a genome is inherited.
+    pub mutation_rate: f32,
+    /// Width of the gaussian step of a mutated gene (relative for traits).
+    pub mutation_magnitude: f32,
+    pub sensors: bool,
+    pub actuators: bool,
+    pub scalar_fields: Vec<ScalarFieldConfig>,
+    pub field_overlay: Option<usize>,
+    pub evolution: EvolutionConfig,
 }
 
 impl Default for SimConfig {
@@ -272,6 +1043,28 @@ impl Default for SimConfig {
             molecules_init_num: MOLECULE_NUM,
             molecule_min_num: MOLECULE_NUM_MIN,
             molecule_speed: MOLECULE_SPEED,
+            molecule_turn: MOLECULE_TURN,
+            force_fields: default_force_fields(),
+            potential: Potential::None,
+            charge_k: COULOMB_K,
+            boundary: BoundaryMode::Walls,
+            time_scale: 1.0,
+            thermostat: Thermostat::None,
+            target_temperature: TEMPERATURE,
+            energy_tolerance: ENERGY_TOLERANCE,
+            rocks_num: ROCKS_NUM,
+            sources_num: SOURCES_NUM,
+            sources_min_num: SOURCES_NUM_MIN,
+            reactions: true,
+            metabolism: true,
+            reproduction: true,
+            mutation_rate: MUTATION_RATE,
+            mutation_magnitude: MUTATION_MAGNITUDE,
+            sensors: false,
+            actuators: false,
+            scalar_fields: default_scalar_fields(),
+            field_overlay: None,
+            evolution: EvolutionConfig::new(),
         }
     }
 }
@@ -282,6 +1075,28 @@ impl SimConfig {
             molecules_init_num: molecules_num,
             molecule_min_num: molecules_min_num,
             molecule_speed: molecule_speed,
+            molecule_turn: molecule_turn,
+            force_fields: default_force_fields(),
+            potential: Potential::None,
+            charge_k: COULOMB_K,
+            boundary: BoundaryMode::Walls,
+            time_scale: 1.0,
+            thermostat: Thermostat::None,
+            target_temperature: TEMPERATURE,
+            energy_tolerance: ENERGY_TOLERANCE,
+            rocks_num: ROCKS_NUM,
+            sources_num: sources_num,
+            sources_min_num: sources_min_num,
+            reactions: true,
+            metabolism: true,
+            reproduction: true,
+            mutation_rate: MUTATION_RATE,
+            mutation_magnitude: MUTATION_MAGNITUDE,
+            sensors: false,
+            actuators: false,
+            scalar_fields: default_scalar_fields(),
+            field_overlay: None,
+            evolution: EvolutionConfig::new(),
         }
     }
 }
@@ -292,7 +1107,27 @@ pub struct SimState {
     pub molecules_num: i32,
     pub sources_num: i32,
     pub physics_num: i32,
+    pub bonds_num: i32,
+    pub reactions_num: i32,
+    pub compounds_num: i32,
+    pub decays_num: i32,
+    pub deaths_num: i32,
+    pub births_num: i32,
+    pub population: PopulationHistory,
+    pub energy: EnergyLedger,
+    pub field_totals: Vec<f32>,
+    pub generation: u32,
+    pub generation_time: f64,
+    pub fitness: Vec<GenerationStats>,
+    pub temperature: f32,
+    pub wall_pressure: [f32; 4],
+    pub collision_rate: f32,
+    pub impact_energy_rate: f32,
+    pub impact_max: f32,
+    pub impact_mean: f32,
+    pub selected_impact: f32,
     pub sim_time: f64,
+    pub running: bool,
     pub fps: i32,
     pub dt: f32,
 }
@@ -304,7 +1139,27 @@ impl SimState {
             molecules_num: 0,
             sources_num: 0,
             physics_num: 0,
+            bonds_num: 0,
+            reactions_num: 0,
+            compounds_num: 0,
+            decays_num: 0,
+            deaths_num: 0,
+            births_num: 0,
+            population: PopulationHistory::new(POPULATION_SAMPLES),
+            energy: EnergyLedger::new(),
+            field_totals: vec![],
+            generation: 0,
+            generation_time: 0.0,
+            fitness: vec![],
+            temperature: 0.0,
+            wall_pressure: [0.0; 4],
+            collision_rate: 0.0,
+            impact_energy_rate: 0.0,
+            impact_max: 0.0,
+            impact_mean: 0.0,
+            selected_impact: 0.0,
             sim_time: 0.0,
+            running: false,
             fps: 0,
             dt: 0.0,
         }
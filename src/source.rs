@@ -12,6 +12,8 @@ use crate::kinetic::{Detection, contact_circles};
 use crate::util::*;
 use crate::consts::*;
 use crate::timer::*;
+use crate::world::*;
+use rapier2d::prelude::RigidBodyHandle;
 
 pub struct Source {
     pub pos: Vec2,
@@ -22,6 +24,7 @@ pub struct Source {
     pub color: color::Color,
     pub shape: Ball,
     pub alife: bool,
+    pub physics_handle: Option<RigidBodyHandle>,
 }
 
 impl Source {
@@ -36,23 +39,28 @@ impl Source {
             color: YELLOW,
             shape: Ball { radius: s },
             alife: true,
+            physics_handle: None,
         }
     }
     pub fn draw(&self) {
         let x0 = self.pos.x;
         let y0 = self.pos.y;
-        draw_circle(x0, y0, self.size, self.color);
+        let fill = (self.eng / self.max_eng).clamp(0.0, 1.0);
+        draw_circle(x0, y0, self.size, Color::new(self.color.r, self.color.g, self.color.b, 0.2 + 0.8*fill));
+        draw_circle_lines(x0, y0, self.size, 1.0, self.color);
     }
     pub fn update(&mut self, _dt: f32){
-        self.pos = wrap_around(&self.pos);
         if self.eng <= 0.0 {
             self.eng = 0.0;
             self.alife = false;
         }
     }
 
-    pub fn drain_eng(&mut self, eng_loss: f32) {
-        self.eng -= eng_loss;
+    /// Takes up to `eng_loss` energy from the source and returns the amount taken.
+    pub fn drain_eng(&mut self, eng_loss: f32) -> f32 {
+        let drained = eng_loss.min(self.eng).max(0.0);
+        self.eng -= drained;
+        return drained;
     }
     
     pub fn update_collision(&mut self, collision_normal: &Vec2, penetration: f32, dt: f32) {
@@ -62,10 +70,22 @@ impl Source {
 
 
 
+impl Default for Source {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
 pub struct SourcesBox {
     pub sources: HashMap<u64, Source>
 }
 
+impl Default for SourcesBox {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
 impl SourcesBox {
     pub fn new() -> Self {
         Self {
@@ -73,15 +93,17 @@ impl SourcesBox {
         }
     }
 
-    pub fn add_many(&mut self, source_num: usize) {
+    pub fn add_many(&mut self, source_num: usize, physics_world: &mut World) {
         for _ in 0..source_num {
             let source = Source::new();
-            _ = self.add_source(source);
+            _ = self.add_source(source, physics_world);
         }
     }
 
-    pub fn add_source(&mut self, source: Source) -> u64 {
+    pub fn add_source(&mut self, mut source: Source, physics_world: &mut World) -> u64 {
         let key: u64 = thread_rng().gen::<u64>();
+        let handle = physics_world.add_source_body(key, &source.pos, source.size);
+        source.physics_handle = Some(handle);
         self.sources.insert(key, source);
         return key;
     }
@@ -90,15 +112,27 @@ impl SourcesBox {
         return self.sources.get(&id);
     }
 
-    pub fn remove(&mut self, id: u64) {
-        self.sources.remove(&id);
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut Source> {
+        return self.sources.get_mut(&id);
+    }
+
+    pub fn remove(&mut self, id: u64, physics_world: &mut World) {
+        if let Some(source) = self.sources.remove(&id) {
+            if let Some(handle) = source.physics_handle {
+                physics_world.remove_physics_object(handle);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.sources.clear();
     }
 
-    pub fn get_iter(&self) -> Iter<u64, Source> {
+    pub fn get_iter(&self) -> Iter<'_, u64, Source> {
         return self.sources.iter();
     }
     
-    pub fn get_iter_mut(&mut self) -> IterMut<u64, Source> {
+    pub fn get_iter_mut(&mut self) -> IterMut<'_, u64, Source> {
         return self.sources.iter_mut();
     }
 
@@ -0,0 +1,243 @@
+#![allow(unused)]
+
+use std::fs;
+use std::collections::VecDeque;
+use macroquad::{prelude::*, color};
+use crate::consts::*;
+
+
+#[derive(Clone)]
+pub struct Species {
+    pub name: String,
+    pub symbol: String,
+    pub radius: f32,
+    pub density: f32,
+    pub color: color::Color,
+    pub field_range: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub valence: u32,
+    /// Half-life in seconds of simulation time, zero for a stable species.
+    pub half_life: f32,
+    pub decay_energy: f32,
+    pub decay_products: Vec<usize>,
+}
+
+impl Species {
+    /// Parses one comma separated line of the species file. The last three fields
+    /// (half-life, decay energy and decay products) are optional and returned as names,
+    /// because products may be defined further down the file.
+    fn from_line(line: &str) -> Result<(Self, Vec<String>), String> {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != 9 && fields.len() != 12 {
+            return Err(format!("expected 9 or 12 fields, found {}", fields.len()));
+        }
+        let number = |i: usize| -> Result<f32, String> {
+            return fields[i].parse::<f32>().map_err(|_| format!("invalid number '{}'", fields[i]));
+        };
+        let (half_life, decay_energy, decay_names) = match fields.len() {
+            12 => (number(9)?, number(10)?, fields[11].split_whitespace().map(|s| s.to_string()).collect()),
+            _ => (0.0, 0.0, vec![]),
+        };
+        let species = Self {
+            name: fields[0].to_string(),
+            symbol: fields[1].to_string(),
+            radius: number(2)?,
+            density: number(3)?,
+            color: parse_hex_color(fields[4])?,
+            field_range: number(5)?,
+            restitution: number(6)?,
+            friction: number(7)?,
+            valence: fields[8].parse::<u32>().map_err(|_| format!("invalid valence '{}'", fields[8]))?,
+            half_life: half_life,
+            decay_energy: decay_energy,
+            decay_products: vec![],
+        };
+        return Ok((species, decay_names));
+    }
+
+    pub fn is_stable(&self) -> bool {
+        return self.half_life <= 0.0 || self.decay_products.is_empty();
+    }
+
+    /// Draws a random lifetime from the exponential distribution given by the half-life.
+    pub fn random_lifetime(&self) -> f32 {
+        let u: f32 = rand::gen_range(f32::EPSILON, 1.0);
+        return -self.half_life / std::f32::consts::LN_2 * u.ln();
+    }
+}
+
+fn parse_hex_color(text: &str) -> Result<color::Color, String> {
+    let hex = text.trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => {
+            return Ok(Color::from_rgba((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255));
+        },
+        _ => {
+            return Err(format!("invalid color '{}'", text));
+        },
+    }
+}
+
+pub struct SpeciesRegistry {
+    species: Vec<Species>,
+}
+
+impl SpeciesRegistry {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut species: Vec<Species> = vec![];
+        let mut decays: Vec<(usize, Vec<String>)> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (entry, decay_names) = Species::from_line(line).map_err(|e| format!("line {}: {}", i+1, e))?;
+            decays.push((i+1, decay_names));
+            species.push(entry);
+        }
+        if species.is_empty() {
+            return Err("no species defined".to_string());
+        }
+        let mut registry = Self { species };
+        for (id, (line, names)) in decays.into_iter().enumerate() {
+            let mut products: Vec<usize> = vec![];
+            for name in names {
+                products.push(registry.find(&name).ok_or(format!("line {}: unknown decay product '{}'", line, name))?);
+            }
+            registry.species[id].decay_products = products;
+        }
+        return Ok(registry);
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        return Self::parse(&text);
+    }
+
+    /// Loads the species file and falls back to the copy built into the binary. The
+    /// error is returned along with the built-in species so it can be shown to the user.
+    pub fn load_or_default(path: &str) -> (Self, Option<String>) {
+        match Self::load(path) {
+            Ok(registry) => (registry, None),
+            Err(err) => {
+                let registry = Self::parse(include_str!("../assets/data/species.csv")).expect("built-in species file is invalid");
+                return (registry, Some(format!("species registry: {}, using built-in species", err)));
+            },
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Species> {
+        return self.species.get(id);
+    }
+
+    /// Maps an id from external data onto a valid one; the registry is never empty.
+    pub fn clamp_id(&self, id: usize) -> usize {
+        return id.min(self.species.len()-1);
+    }
+
+    /// Species of an id, falling back to the last species for unknown ids.
+    pub fn get_clamped(&self, id: usize) -> &Species {
+        return &self.species[self.clamp_id(id)];
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        return self.species.iter().position(|s| s.name == name || s.symbol == name);
+    }
+
+    pub fn random_id(&self) -> usize {
+        return rand::gen_range(0, self.species.len());
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Species> {
+        return self.species.iter();
+    }
+
+    pub fn count(&self) -> usize {
+        return self.species.len();
+    }
+}
+
+/// Number of molecules of every species sampled at regular intervals.
+pub struct PopulationHistory {
+    pub times: VecDeque<f64>,
+    pub counts: Vec<VecDeque<usize>>,
+    capacity: usize,
+}
+
+impl PopulationHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            times: VecDeque::new(),
+            counts: vec![],
+            capacity: capacity,
+        }
+    }
+
+    pub fn add_sample(&mut self, time: f64, counts: &[usize]) {
+        while self.counts.len() < counts.len() {
+            self.counts.push(VecDeque::from(vec![0; self.times.len()]));
+        }
+        self.times.push_back(time);
+        for (history, count) in self.counts.iter_mut().zip(counts.iter()) {
+            history.push_back(*count);
+        }
+        if self.times.len() > self.capacity {
+            self.times.pop_front();
+            for history in self.counts.iter_mut() {
+                history.pop_front();
+            }
+        }
+    }
+
+    pub fn last_time(&self) -> Option<f64> {
+        return self.times.back().copied();
+    }
+
+    pub fn clear(&mut self) {
+        self.times.clear();
+        for history in self.counts.iter_mut() {
+            history.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPECIES: &str = "# name, symbol, radius, density, color, field_range, restitution, friction, valence
+Hydrogen, H, 2.0, 1.0, #FFFFFF, 24, 0.9, 0.1, 1
+Oxygen, O, 4.0, 1.2, #FF0D0D, 40, 0.9, 0.1, 2
+Ozone, O3, 5.5, 1.1, #80C0FF, 40, 0.9, 0.1, 0, 30, 1200, O2 O
+Dioxygen, O2, 4.8, 1.1, #FF6060, 40, 0.9, 0.1, 0
+";
+
+    #[test]
+    fn parses_species_and_decay_products() {
+        let registry = SpeciesRegistry::parse(SPECIES).unwrap();
+        assert_eq!(registry.count(), 4);
+        let oxygen = registry.get(registry.find("O").unwrap()).unwrap();
+        assert_eq!(oxygen.name, "Oxygen");
+        assert_eq!(oxygen.valence, 2);
+        assert!(oxygen.is_stable());
+        let ozone = registry.get(registry.find("Ozone").unwrap()).unwrap();
+        assert!(!ozone.is_stable());
+        assert_eq!(ozone.decay_products, vec![registry.find("O2").unwrap(), registry.find("O").unwrap()]);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert!(SpeciesRegistry::parse("").is_err());
+        assert!(SpeciesRegistry::parse("Hydrogen, H, 2.0").is_err());
+        assert!(SpeciesRegistry::parse("Hydrogen, H, x, 1.0, #FFFFFF, 24, 0.9, 0.1, 1").is_err());
+        assert!(SpeciesRegistry::parse("Ozone, O3, 5.5, 1.1, #80C0FF, 40, 0.9, 0.1, 0, 30, 1200, Xe").is_err());
+    }
+
+    #[test]
+    fn clamps_unknown_ids() {
+        let registry = SpeciesRegistry::parse(SPECIES).unwrap();
+        assert_eq!(registry.clamp_id(1), 1);
+        assert_eq!(registry.clamp_id(99), 3);
+    }
+}
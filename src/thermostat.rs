@@ -0,0 +1,81 @@
+#![allow(unused)]
+
+use rapier2d::prelude::*;
+use crate::util::*;
+use crate::world::*;
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Thermostat {
+    None,
+    Rescale,
+    Berendsen { tau: f32 },
+    Langevin { gamma: f32 },
+}
+
+impl Thermostat {
+    pub fn default_berendsen() -> Self {
+        return Thermostat::Berendsen { tau: 1.0 };
+    }
+
+    pub fn default_langevin() -> Self {
+        return Thermostat::Langevin { gamma: 0.5 };
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Thermostat::None => "None",
+            Thermostat::Rescale => "Velocity Rescaling",
+            Thermostat::Berendsen { .. } => "Berendsen",
+            Thermostat::Langevin { .. } => "Langevin",
+        }
+    }
+
+    /// Moves body velocities toward the target temperature over one time step.
+    pub fn apply(&self, world: &mut World, target: f32, dt: f32) {
+        let temperature = world.get_kinetic_temperature();
+        match *self {
+            Thermostat::None => {},
+            Thermostat::Rescale => {
+                if temperature > 0.0 {
+                    scale_velocities(world, (target / temperature).sqrt());
+                }
+            },
+            Thermostat::Berendsen { tau } => {
+                if temperature > 0.0 {
+                    let lambda = (1.0 + dt / tau.max(dt) * (target / temperature - 1.0)).max(0.0).sqrt();
+                    scale_velocities(world, lambda);
+                }
+            },
+            Thermostat::Langevin { gamma } => {
+                for (_, rb) in world.rigid_bodies.iter_mut() {
+                    if !rb.is_dynamic() {
+                        continue;
+                    }
+                    let sigma = (2.0 * gamma * target * dt / rb.mass()).sqrt();
+                    let noise = vector![random_gauss(), random_gauss()] * sigma;
+                    let linvel = *rb.linvel() * (1.0 - gamma * dt) + noise;
+                    rb.set_linvel(linvel, true);
+                }
+            },
+        }
+    }
+}
+
+fn scale_velocities(world: &mut World, lambda: f32) {
+    for (_, rb) in world.rigid_bodies.iter_mut() {
+        if rb.is_dynamic() {
+            let linvel = *rb.linvel() * lambda;
+            rb.set_linvel(linvel, true);
+        }
+    }
+}
+
+pub fn thermostat_variants() -> [Thermostat; 4] {
+    return [
+        Thermostat::None,
+        Thermostat::Rescale,
+        Thermostat::default_berendsen(),
+        Thermostat::default_langevin(),
+    ];
+}
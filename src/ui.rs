@@ -1,18 +1,25 @@
 
 use std::path::Path;
 
-use egui_macroquad;
 use macroquad::prelude::*;
 use egui::{self, Context, Style};
 use egui::{RichText, Color32};
+use egui::plot::{Plot, Line, PlotPoints, Legend};
 use egui_extras::image::RetainedImage;
 use image::open;
 use macroquad::ui::StyleBuilder;
 
 use crate::particle::Molecule;
-use crate::consts::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::consts::{SCREEN_WIDTH, SCREEN_HEIGHT, WORLD_W, WORLD_H, GRAV, MAX_CHARGE};
 use crate::{progress_bar::*, Signals};
 use crate::sim::*;
+use crate::force_field::*;
+use crate::potential::*;
+use crate::thermostat::*;
+use crate::species::*;
+use crate::compound::*;
+use crate::evolution::*;
+use crate::world::BoundaryMode;
 
 
 static V: Vec2 = Vec2::ZERO;
@@ -21,6 +28,7 @@ pub struct UISystem {
     pub state: UIState,
     pub pointer_over: bool,
     temp_sim_name: String,
+    temp_boundary: Option<BoundaryMode>,
 }
 
 impl UISystem {
@@ -29,24 +37,29 @@ impl UISystem {
             state: UIState::new(),
             pointer_over: false,
             temp_sim_name: String::new(),
+            temp_boundary: None,
         }
     }
     
-    pub fn ui_process(&mut self, sim_state: &SimState, agent: Option<&Molecule>, signals: &mut Signals) {
+    pub fn ui_process(&mut self, sim_state: &SimState, agent: Option<(u64, &Molecule)>, compound: Option<&Compound>, signals: &mut Signals, config: &mut SimConfig, species: &SpeciesRegistry) {
         egui_macroquad::ui(|egui_ctx| {
             self.pointer_over = egui_ctx.is_pointer_over_area();
             self.build_top_menu(egui_ctx, &sim_state.sim_name);
             self.build_quit_window(egui_ctx);
-            self.build_monit_window(egui_ctx, sim_state.fps, sim_state.dt, sim_state.sim_time, sim_state.molecules_num, sim_state.physics_num);
+            self.build_data_errors_window(egui_ctx);
+            self.build_monit_window(egui_ctx, sim_state, species);
             self.build_mouse_window(egui_ctx);
-            match agent {
-                Some(agent) => {
-                    self.build_inspect_window(egui_ctx, agent)
-                },
-                None => {}
+            if let Some(agent) = agent {
+                self.build_inspect_window(egui_ctx, agent, compound, sim_state.selected_impact, signals, species);
             }
-            self.build_create_window(egui_ctx, signals);
-            self.build_new_sim_window(egui_ctx, signals);
+            self.build_create_window(egui_ctx, signals, species);
+            self.build_new_sim_window(egui_ctx, signals, config);
+            self.build_fields_window(egui_ctx, config);
+            self.build_interactions_window(egui_ctx, config);
+            self.build_time_window(egui_ctx, sim_state, signals, config);
+            self.build_thermostat_window(egui_ctx, sim_state, config);
+            self.build_scalar_fields_window(egui_ctx, sim_state, config);
+            self.build_evolution_window(egui_ctx, sim_state, config);
         });
     }
 
@@ -64,10 +77,8 @@ impl UISystem {
                     if ui.button(RichText::new("New Simulation").strong().color(Color32::BLUE)).clicked() {
                         self.state.new_sim = true;
                     }
-                    if ui.button(RichText::new("Load Simulation").weak().color(Color32::from_gray(100))).clicked() {
-                    }
-                    if ui.button(RichText::new("Save Simulation").weak().color(Color32::from_gray(100))).clicked() {
-                    }
+                    _ = ui.button(RichText::new("Load Simulation").weak().color(Color32::from_gray(100)));
+                    _ = ui.button(RichText::new("Save Simulation").weak().color(Color32::from_gray(100)));
                     if ui.button(RichText::new("Quit").color(Color32::RED).strong()).clicked() {
                         self.state.quit = true;
                     }
@@ -88,6 +99,24 @@ impl UISystem {
                     if ui.button(RichText::new("Creator").strong().color(Color32::from_gray(200))).clicked() {
                         self.state.create = !self.state.create;
                     }
+                    if ui.button(RichText::new("Force Fields").strong().color(Color32::from_gray(200))).clicked() {
+                        self.state.fields = !self.state.fields;
+                    }
+                    if ui.button(RichText::new("Interactions").strong().color(Color32::from_gray(200))).clicked() {
+                        self.state.interactions = !self.state.interactions;
+                    }
+                    if ui.button(RichText::new("Time").strong().color(Color32::from_gray(200))).clicked() {
+                        self.state.time = !self.state.time;
+                    }
+                    if ui.button(RichText::new("Thermostat").strong().color(Color32::from_gray(200))).clicked() {
+                        self.state.thermostat = !self.state.thermostat;
+                    }
+                    if ui.button(RichText::new("Scalar Fields").strong().color(Color32::from_gray(200))).clicked() {
+                        self.state.scalar_fields = !self.state.scalar_fields;
+                    }
+                    if ui.button(RichText::new("Evolution").strong().color(Color32::from_gray(200))).clicked() {
+                        self.state.evolution = !self.state.evolution;
+                    }
                 });
                 ui.add_space(10.0);
                 ui.separator();
@@ -95,8 +124,14 @@ impl UISystem {
         });
     }
 
-    fn build_monit_window(&self, egui_ctx: &Context, fps: i32, delta: f32, time: f64, molecules_num: i32, physics_num: i32) {
+    fn build_monit_window(&self, egui_ctx: &Context, sim_state: &SimState, species: &SpeciesRegistry) {
         if self.state.performance {
+            let fps = sim_state.fps;
+            let delta = sim_state.dt;
+            let time = sim_state.sim_time;
+            let molecules_num = sim_state.molecules_num;
+            let physics_num = sim_state.physics_num;
+            let bonds_num = sim_state.bonds_num;
             egui::Window::new("Monitor").default_pos((5.0, 100.0))
             .default_width(125.0)
             .show(egui_ctx, |ui| {
@@ -107,21 +142,99 @@ impl UISystem {
                 ui.label(format!("TIME: {}", time.round()));
                 ui.separator();
                 ui.label(format!("AGENTS: {}", molecules_num));
+                ui.label(format!("SOURCES: {}", sim_state.sources_num));
                 ui.separator();
                 ui.label(format!("PHYSICS OBJECTS: {}", physics_num));
+                ui.separator();
+                ui.label(format!("BONDS: {}", bonds_num));
+                ui.label(format!("COMPOUNDS: {}", sim_state.compounds_num));
+                ui.label(format!("REACTIONS: {}", sim_state.reactions_num));
+                ui.label(format!("DECAYS: {}", sim_state.decays_num));
+                ui.label(format!("DEATHS: {}", sim_state.deaths_num));
+                ui.label(format!("BIRTHS: {}", sim_state.births_num));
+                ui.separator();
+                ui.label(format!("TEMPERATURE: {:.0}", sim_state.temperature));
+                ui.separator();
+                let energy = &sim_state.energy;
+                ui.label(format!("ENERGY K: {:.0} | U: {:.0}", energy.kinetic, energy.potential));
+                ui.label(format!("ENERGY CHEM: {:.0} | TOTAL: {:.0}", energy.chemical, energy.total()));
+                ui.label(format!("EXTERNAL WORK: {:.0} | DRIFT: {:.0}", energy.external, energy.drift));
+                if energy.warning {
+                    ui.label(RichText::new(format!("ENERGY IMBALANCE: {:.1}%", energy.step_error*100.0)).strong().color(Color32::RED));
+                }
+                ui.separator();
+                let p = sim_state.wall_pressure;
+                ui.label(format!("PRESSURE L: {:.1} | R: {:.1}", p[0], p[1]));
+                ui.label(format!("PRESSURE T: {:.1} | B: {:.1}", p[2], p[3]));
+                ui.separator();
+                ui.label(format!("COLLISIONS/s: {:.1}", sim_state.collision_rate));
+                ui.label(format!("IMPACT ENERGY/s: {:.0}", sim_state.impact_energy_rate));
+                ui.label(format!("IMPACT MAX: {:.0} | MEAN: {:.0}", sim_state.impact_max, sim_state.impact_mean));
+                ui.separator();
+                ui.label(RichText::new("POPULATION").strong());
+                self.build_population_plot(ui, &sim_state.population, species);
             });
         }    
     }
 
-    fn build_inspect_window(&self, egui_ctx: &Context, agent: &Molecule) {
+    fn build_population_plot(&self, ui: &mut egui::Ui, population: &PopulationHistory, species: &SpeciesRegistry) {
+        Plot::new("population")
+        .height(140.0)
+        .width(260.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .show(ui, |plot_ui| {
+            for (species, counts) in species.iter().zip(population.counts.iter()) {
+                if counts.iter().all(|n| *n == 0) {
+                    continue;
+                }
+                let points: PlotPoints = population.times.iter().zip(counts.iter()).map(|(t, n)| [*t, *n as f64]).collect();
+                let color = Color32::from_rgb((species.color.r*255.0) as u8, (species.color.g*255.0) as u8, (species.color.b*255.0) as u8);
+                plot_ui.line(Line::new(points).name(&species.symbol).color(color));
+            }
+        });
+    }
+
+    fn build_inspect_window(&self, egui_ctx: &Context, (id, agent): (u64, &Molecule), compound: Option<&Compound>, impact: f32, signals: &mut Signals, species: &SpeciesRegistry) {
         if self.state.inspect {
             let rot = agent.rot;
             let size = agent.size;
+            let charge = agent.charge;
+            let species_name = species.get(agent.species).map_or(String::from("?"), |s| s.name.to_owned());
             egui::Window::new("Inspector").default_pos((5.0, 200.0))
             .default_width(125.0)
             .show(egui_ctx, |ui| {
+                ui.label(format!("SPECIES: {}", species_name));
                 ui.label(format!("ROTATION: {}", ((rot*10.0).round())/10.0));
                 ui.label(format!("SIZE: {}", size));
+                ui.label(format!("CHARGE: {}", charge));
+                ui.label(format!("AGE: {:.1}s", agent.lifetime));
+                ui.label(format!("IMPACT: {:.0}", impact));
+                ui.add(ProgressBar::new(agent.eng / agent.max_eng).text(format!("ENERGY: {:.0}/{:.0}", agent.eng, agent.max_eng)));
+                if let Some(compound) = compound {
+                    ui.separator();
+                    ui.label(RichText::new(format!("COMPOUND: {}", compound.formula)).strong().color(Color32::YELLOW));
+                    ui.label(format!("ATOMS: {}", compound.members.len()));
+                    ui.label(format!("BONDS: {}", compound.bonds.len()));
+                    ui.label(format!("MASS: {:.1}", compound.mass));
+                    ui.label(format!("VELOCITY: [{:.1} | {:.1}]", compound.velocity.x, compound.velocity.y));
+                    ui.label(format!("AGE: {:.1}s", compound.age));
+                    ui.separator();
+                    egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                        for (member, species_id) in compound.members.iter().zip(compound.species.iter()) {
+                            let symbol = species.get(*species_id).map_or("?", |s| s.symbol.as_str());
+                            let mut text = RichText::new(format!("{} #{:04}", symbol, member % 10000));
+                            if compound.are_bonded(id, *member) {
+                                text = text.color(Color32::LIGHT_GREEN);
+                            }
+                            if ui.selectable_label(*member == id, text).clicked() {
+                                signals.select_molecule = Some(*member);
+                            }
+                        }
+                    });
+                }
             });
         }    
     }
@@ -159,9 +272,25 @@ impl UISystem {
         }    
     }
 
-    fn build_new_sim_window(&mut self, egui_ctx: &Context, signals: &mut Signals) {
+    fn build_data_errors_window(&mut self, egui_ctx: &Context) {
+        if !self.state.data_errors.is_empty() {
+            egui::Window::new("Data Files").default_pos((SCREEN_WIDTH/2.0-150.0, SCREEN_HEIGHT/3.0))
+            .default_width(300.0)
+            .show(egui_ctx, |ui| {
+                for error in self.state.data_errors.iter() {
+                    ui.label(RichText::new(error).color(Color32::YELLOW));
+                }
+                if ui.button(RichText::new("OK").color(Color32::WHITE)).clicked() {
+                    self.state.data_errors.clear();
+                }
+            });
+        }
+    }
+
+    fn build_new_sim_window(&mut self, egui_ctx: &Context, signals: &mut Signals, config: &mut SimConfig) {
         if self.state.new_sim {
             let mut sim_name: String = String::new();
+            let mut boundary = *self.temp_boundary.get_or_insert(config.boundary);
             egui::Window::new("New Simulation").default_pos((SCREEN_WIDTH/2.0-65.0, SCREEN_HEIGHT/4.0))
             .default_width(125.0)
             .show(egui_ctx, |ui| {
@@ -184,9 +313,16 @@ impl UISystem {
                         signals.new_sim = true;
                         signals.new_sim_name = String::from(&self.temp_sim_name);
                         self.temp_sim_name=String::new();
+                        config.boundary = boundary;
                     }
                     //let response = txt.text_edit_singleline(&mut sim_name);
                 });
+                ui.horizontal(|modes| {
+                    modes.label("BOUNDARY:");
+                    for mode in [BoundaryMode::Walls, BoundaryMode::Periodic, BoundaryMode::Open] {
+                        modes.radio_value(&mut boundary, mode, mode.name());
+                    }
+                });
                 ui.horizontal(|mid| {
                     mid.columns(2, |columns| {
                         if columns[0].button(RichText::new("No").color(Color32::WHITE)).clicked() {
@@ -198,14 +334,17 @@ impl UISystem {
                             signals.new_sim = true;
                             signals.new_sim_name = String::from(&self.temp_sim_name);
                             self.temp_sim_name=String::new();
+                            config.boundary = boundary;
                         }
                     });
                 });
             });
+            // The boundary is only applied when the new simulation is confirmed.
+            self.temp_boundary = if self.state.new_sim { Some(boundary) } else { None };
         }    
     }
 
-    fn build_create_window(&self, egui_ctx: &Context, signals: &mut Signals) {
+    fn build_create_window(&mut self, egui_ctx: &Context, signals: &mut Signals, species: &SpeciesRegistry) {
         if self.state.create {
             egui::Window::new("Creator").default_pos((5.0, 450.0))
             .default_width(125.0)
@@ -213,17 +352,295 @@ impl UISystem {
                 ui.horizontal(|head| {
                     head.heading("Spawn new creature");
                 });
+                let selected_name = species.get(self.state.spawn_species).map_or(String::from("?"), |s| s.name.to_owned());
+                egui::ComboBox::from_label("SPECIES")
+                .selected_text(selected_name)
+                .show_ui(ui, |ui| {
+                    for (i, entry) in species.iter().enumerate() {
+                        ui.selectable_value(&mut self.state.spawn_species, i, format!("{} ({})", entry.name, entry.symbol));
+                    }
+                });
+                ui.add(egui::Slider::new(&mut self.state.spawn_charge, -MAX_CHARGE..=MAX_CHARGE).step_by(1.0).text("CHARGE"));
                 ui.horizontal(|mid| {
                     mid.style_mut().visuals.extreme_bg_color = Color32::BLUE;
                     if mid.button(RichText::new("SPAWN").strong().color(Color32::WHITE)).clicked() {
                         //self.state.create = false;
                         signals.spawn_molecule = true;
+                        signals.spawn_charge = self.state.spawn_charge;
+                        signals.spawn_species = self.state.spawn_species;
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|head| {
+                    head.heading("Place obstacle");
+                });
+                ui.horizontal(|mid| {
+                    if mid.selectable_label(signals.spawn_box, RichText::new("BOX").strong().color(Color32::WHITE)).clicked() {
+                        let armed = !signals.spawn_box;
+                        (signals.spawn_box, signals.spawn_circle, signals.spawn_rock) = (armed, false, false);
+                    }
+                    if mid.selectable_label(signals.spawn_circle, RichText::new("CIRCLE").strong().color(Color32::WHITE)).clicked() {
+                        let armed = !signals.spawn_circle;
+                        (signals.spawn_box, signals.spawn_circle, signals.spawn_rock) = (false, armed, false);
+                    }
+                    if mid.selectable_label(signals.spawn_rock, RichText::new("ROCK").strong().color(Color32::WHITE)).clicked() {
+                        let armed = !signals.spawn_rock;
+                        (signals.spawn_box, signals.spawn_circle, signals.spawn_rock) = (false, false, armed);
+                    }
+                    if mid.button(RichText::new("CLEAR").strong().color(Color32::RED)).clicked() {
+                        signals.clear_obstacles = true;
                     }
                 });
+                if signals.spawn_box || signals.spawn_circle || signals.spawn_rock {
+                    ui.label(RichText::new("CLICK A FREE SPOT IN THE WORLD").color(Color32::YELLOW));
+                }
             });
         }    
     }
 
+    fn build_fields_window(&self, egui_ctx: &Context, config: &mut SimConfig) {
+        if self.state.fields {
+            egui::Window::new("Force Fields").default_pos((SCREEN_WIDTH-215.0, 100.0))
+            .default_width(200.0)
+            .show(egui_ctx, |ui| {
+                let mut to_remove: Option<usize> = None;
+                for (i, field) in config.force_fields.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| {
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_source("field_kind")
+                            .selected_text(field.name())
+                            .show_ui(ui, |ui| {
+                                for variant in force_field_variants() {
+                                    if ui.selectable_label(field.name() == variant.name(), variant.name()).clicked() && field.name() != variant.name() {
+                                        *field = variant;
+                                    }
+                                }
+                            });
+                            if ui.button(RichText::new("REMOVE").color(Color32::RED)).clicked() {
+                                to_remove = Some(i);
+                            }
+                        });
+                        match field {
+                            ForceField::None => {},
+                            ForceField::Attractor { center, strength } | ForceField::Vortex { center, strength } => {
+                                ui.add(egui::Slider::new(&mut center.x, 0.0..=WORLD_W).text("X"));
+                                ui.add(egui::Slider::new(&mut center.y, 0.0..=WORLD_H).text("Y"));
+                                ui.add(egui::Slider::new(strength, -2.0*GRAV..=2.0*GRAV).text("STRENGTH"));
+                            },
+                            ForceField::Gravity { acceleration } => {
+                                ui.add(egui::Slider::new(&mut acceleration.x, -100.0..=100.0).text("ACC X"));
+                                ui.add(egui::Slider::new(&mut acceleration.y, -100.0..=100.0).text("ACC Y"));
+                            },
+                            ForceField::Shear { strength } => {
+                                ui.add(egui::Slider::new(strength, -100.0..=100.0).text("STRENGTH"));
+                            },
+                        }
+                        ui.separator();
+                    });
+                }
+                if let Some(i) = to_remove {
+                    config.force_fields.remove(i);
+                }
+                if ui.button(RichText::new("ADD FIELD").strong().color(Color32::WHITE)).clicked() {
+                    config.force_fields.push(ForceField::None);
+                }
+            });
+        }
+    }
+
+    fn build_interactions_window(&self, egui_ctx: &Context, config: &mut SimConfig) {
+        if self.state.interactions {
+            egui::Window::new("Interactions").default_pos((SCREEN_WIDTH-215.0, 400.0))
+            .default_width(200.0)
+            .show(egui_ctx, |ui| {
+                ui.add(egui::Slider::new(&mut config.charge_k, 0.0..=100000.0).text("CHARGE K"));
+                ui.checkbox(&mut config.reactions, "REACTIONS");
+                ui.checkbox(&mut config.metabolism, "METABOLISM");
+                ui.checkbox(&mut config.reproduction, "REPRODUCTION");
+                ui.add(egui::Slider::new(&mut config.mutation_rate, 0.0..=0.5).text("MUTATION RATE"));
+                ui.add(egui::Slider::new(&mut config.mutation_magnitude, 0.0..=1.0).text("MUTATION MAGNITUDE"));
+                ui.checkbox(&mut config.sensors, "SENSORS");
+                ui.checkbox(&mut config.actuators, "ACTUATORS");
+                ui.add(egui::Slider::new(&mut config.molecule_speed, 0.0..=200.0).text("MAX SPEED"));
+                ui.add(egui::Slider::new(&mut config.molecule_turn, 0.0..=10.0).text("MAX TURN"));
+                ui.separator();
+                let potential = &mut config.potential;
+                egui::ComboBox::from_id_source("potential_kind")
+                .selected_text(potential.name())
+                .show_ui(ui, |ui| {
+                    for variant in potential_variants() {
+                        if ui.selectable_label(potential.name() == variant.name(), variant.name()).clicked() && potential.name() != variant.name() {
+                            *potential = variant;
+                        }
+                    }
+                });
+                match potential {
+                    Potential::None => {},
+                    Potential::LennardJones { epsilon, sigma } => {
+                        ui.add(egui::Slider::new(epsilon, 0.0..=500.0).text("EPSILON"));
+                        ui.add(egui::Slider::new(sigma, 1.0..=64.0).text("SIGMA"));
+                    },
+                    Potential::Coulomb { k } => {
+                        ui.add(egui::Slider::new(k, -200000.0..=200000.0).text("K"));
+                    },
+                    Potential::Morse { depth, width, r0 } => {
+                        ui.add(egui::Slider::new(depth, 0.0..=500.0).text("DEPTH"));
+                        ui.add(egui::Slider::new(width, 0.01..=2.0).text("WIDTH"));
+                        ui.add(egui::Slider::new(r0, 1.0..=64.0).text("R0"));
+                    },
+                    Potential::SoftSphere { k, sigma } => {
+                        ui.add(egui::Slider::new(k, 0.0..=5000.0).text("K"));
+                        ui.add(egui::Slider::new(sigma, 1.0..=64.0).text("SIGMA"));
+                    },
+                }
+            });
+        }
+    }
+
+    fn build_time_window(&mut self, egui_ctx: &Context, sim_state: &SimState, signals: &mut Signals, config: &mut SimConfig) {
+        if self.state.time {
+            egui::Window::new("Time").default_pos((SCREEN_WIDTH/2.0-100.0, 40.0))
+            .default_width(200.0)
+            .show(egui_ctx, |ui| {
+                ui.add(egui::Slider::new(&mut config.time_scale, 0.1..=10.0).logarithmic(true).text("TIME SCALE"));
+                ui.horizontal(|row| {
+                    let label = if sim_state.running { "PAUSE" } else { "RESUME" };
+                    if row.button(RichText::new(label).strong().color(Color32::WHITE)).clicked() {
+                        signals.pause = true;
+                    }
+                });
+                ui.horizontal(|row| {
+                    row.add(egui::DragValue::new(&mut self.state.step_frames).clamp_range(1..=1000));
+                    if row.add_enabled(!sim_state.running, egui::Button::new(RichText::new("STEP").strong())).clicked() {
+                        signals.step_frames = self.state.step_frames;
+                    }
+                });
+            });
+        }
+    }
+
+    fn build_thermostat_window(&self, egui_ctx: &Context, sim_state: &SimState, config: &mut SimConfig) {
+        if self.state.thermostat {
+            egui::Window::new("Thermostat").default_pos((SCREEN_WIDTH-215.0, 250.0))
+            .default_width(200.0)
+            .show(egui_ctx, |ui| {
+                ui.label(format!("TEMPERATURE: {:.0}", sim_state.temperature));
+                ui.add(egui::Slider::new(&mut config.target_temperature, 0.0..=100000.0).logarithmic(true).text("TARGET"));
+                ui.add(egui::Slider::new(&mut config.energy_tolerance, 0.001..=1.0).logarithmic(true).text("ENERGY TOLERANCE"));
+                let thermostat = &mut config.thermostat;
+                egui::ComboBox::from_id_source("thermostat_kind")
+                .selected_text(thermostat.name())
+                .show_ui(ui, |ui| {
+                    for variant in thermostat_variants() {
+                        if ui.selectable_label(thermostat.name() == variant.name(), variant.name()).clicked() && thermostat.name() != variant.name() {
+                            *thermostat = variant;
+                        }
+                    }
+                });
+                match thermostat {
+                    Thermostat::None | Thermostat::Rescale => {},
+                    Thermostat::Berendsen { tau } => {
+                        ui.add(egui::Slider::new(tau, 0.05..=10.0).logarithmic(true).text("TAU"));
+                    },
+                    Thermostat::Langevin { gamma } => {
+                        ui.add(egui::Slider::new(gamma, 0.01..=10.0).logarithmic(true).text("GAMMA"));
+                    },
+                }
+            });
+        }
+    }
+
+    fn build_scalar_fields_window(&self, egui_ctx: &Context, sim_state: &SimState, config: &mut SimConfig) {
+        if self.state.scalar_fields {
+            egui::Window::new("Scalar Fields").default_pos((SCREEN_WIDTH-215.0, 550.0))
+            .default_width(200.0)
+            .show(egui_ctx, |ui| {
+                let overlay_name = config.field_overlay.and_then(|i| config.scalar_fields.get(i)).map_or("None", |f| f.name.as_str()).to_string();
+                egui::ComboBox::from_label("OVERLAY")
+                .selected_text(overlay_name)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut config.field_overlay, None, "None");
+                    for (i, field) in config.scalar_fields.iter().enumerate() {
+                        ui.selectable_value(&mut config.field_overlay, Some(i), &field.name);
+                    }
+                });
+                for (i, field) in config.scalar_fields.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| {
+                        ui.separator();
+                        let total = sim_state.field_totals.get(i).copied().unwrap_or(0.0);
+                        ui.label(RichText::new(format!("{}: {:.1}", field.name.to_uppercase(), total)).strong());
+                        ui.add(egui::Slider::new(&mut field.diffusion, 0.0..=2000.0).text("DIFFUSION"));
+                        ui.add(egui::Slider::new(&mut field.decay, 0.0..=2.0).text("DECAY"));
+                    });
+                }
+            });
+        }
+    }
+
+    fn build_evolution_window(&self, egui_ctx: &Context, sim_state: &SimState, config: &mut SimConfig) {
+        if self.state.evolution {
+            egui::Window::new("Evolution").default_pos((SCREEN_WIDTH-285.0, 100.0))
+            .default_width(270.0)
+            .show(egui_ctx, |ui| {
+                let evolution = &mut config.evolution;
+                egui::ComboBox::from_label("MODE")
+                .selected_text(evolution.mode.name())
+                .show_ui(ui, |ui| {
+                    for variant in evolution_mode_variants() {
+                        ui.selectable_value(&mut evolution.mode, variant, variant.name());
+                    }
+                });
+                ui.add(egui::Slider::new(&mut evolution.generation_size, 2..=256).text("MOLECULES"));
+                ui.add(egui::Slider::new(&mut evolution.generation_time, 5.0..=600.0).logarithmic(true).text("GENERATION TIME"));
+                ui.add(egui::Slider::new(&mut evolution.elitism, 0..=16).text("ELITISM"));
+                egui::ComboBox::from_label("SELECTION")
+                .selected_text(evolution.selection.name())
+                .show_ui(ui, |ui| {
+                    for variant in selection_variants() {
+                        if ui.selectable_label(evolution.selection.name() == variant.name(), variant.name()).clicked() && evolution.selection.name() != variant.name() {
+                            evolution.selection = variant;
+                        }
+                    }
+                });
+                if let Selection::Tournament { size } = &mut evolution.selection {
+                    ui.add(egui::Slider::new(size, 1..=16).text("TOURNAMENT SIZE"));
+                }
+                egui::ComboBox::from_label("CROSSOVER")
+                .selected_text(evolution.crossover.name())
+                .show_ui(ui, |ui| {
+                    for variant in crossover_variants() {
+                        ui.selectable_value(&mut evolution.crossover, variant, variant.name());
+                    }
+                });
+                ui.separator();
+                ui.label(RichText::new(format!("GENERATION: {}", sim_state.generation)).strong());
+                if evolution.mode == EvolutionMode::Generational {
+                    ui.label(format!("TIME: {:.0}/{:.0}", sim_state.generation_time, evolution.generation_time));
+                }
+                if let Some(last) = sim_state.fitness.last() {
+                    ui.label(format!("FITNESS BEST: {:.1} | MEAN: {:.1}", last.best, last.mean));
+                }
+                self.build_fitness_plot(ui, &sim_state.fitness);
+            });
+        }
+    }
+
+    fn build_fitness_plot(&self, ui: &mut egui::Ui, fitness: &[GenerationStats]) {
+        Plot::new("fitness")
+        .height(140.0)
+        .width(260.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .show(ui, |plot_ui| {
+            let best: PlotPoints = fitness.iter().map(|s| [s.generation as f64, s.best as f64]).collect();
+            let mean: PlotPoints = fitness.iter().map(|s| [s.generation as f64, s.mean as f64]).collect();
+            plot_ui.line(Line::new(best).name("BEST").color(Color32::GREEN));
+            plot_ui.line(Line::new(mean).name("MEAN").color(Color32::LIGHT_BLUE));
+        });
+    }
+
     pub fn ui_draw(&self) {
         egui_macroquad::draw();
     }
@@ -237,10 +654,20 @@ pub struct UIState {
     pub inspect: bool,
     pub mouse: bool,
     pub create: bool,
+    pub fields: bool,
+    pub interactions: bool,
+    pub time: bool,
+    pub thermostat: bool,
+    pub scalar_fields: bool,
+    pub evolution: bool,
     pub quit: bool,
     pub molecules_num: i32,
+    pub spawn_charge: f32,
+    pub spawn_species: usize,
+    pub step_frames: u32,
     pub new_sim: bool,
     pub new_sim_name: String,
+    pub data_errors: Vec<String>,
 }
 
 impl UIState {
@@ -250,10 +677,20 @@ impl UIState {
             inspect: false,
             mouse: false,
             create: false,
+            fields: false,
+            interactions: false,
+            time: false,
+            thermostat: false,
+            scalar_fields: false,
+            evolution: false,
             quit: false,
             molecules_num: 0,
+            spawn_charge: 0.0,
+            spawn_species: 0,
+            step_frames: 1,
             new_sim: false,
             new_sim_name: String::new(),
+            data_errors: vec![],
         }
     }
 }
\ No newline at end of file
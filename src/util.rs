@@ -23,11 +23,26 @@ pub fn random_unit_vec2() -> Vec2 {
     return  Vec2::new(x, y).normalize_or_zero();    
 }
 
-pub fn random_color() -> color::Color {
-    let colors = vec![RED, GREEN, BLUE, YELLOW, ORANGE, GRAY, SKYBLUE, LIME];
-    let num = colors.len();
-    let c = rand::gen_range(0, num);
-    return  colors[c];
+/// Normally distributed random number (Box-Muller transform).
+pub fn random_gauss() -> f32 {
+    let u1: f32 = rand::gen_range(f32::EPSILON, 1.0);
+    let u2: f32 = rand::gen_range(0.0, 1.0);
+    return (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+}
+
+/// Multiplies a value by a random gaussian factor around 1.
+pub fn mutate(value: f32, rate: f32) -> f32 {
+    return value * (1.0 + random_gauss() * rate);
+}
+
+pub fn charge_color(charge: f32) -> color::Color {
+    let intensity = (charge.abs() / MAX_CHARGE).clamp(0.0, 1.0);
+    if charge > 0.0 {
+        return Color::new(0.5+0.5*intensity, 0.5-0.4*intensity, 0.5-0.4*intensity, 1.0);
+    } else if charge < 0.0 {
+        return Color::new(0.5-0.4*intensity, 0.5-0.4*intensity, 0.5+0.5*intensity, 1.0);
+    }
+    return GRAY;
 }
 
 pub fn angle2vec2(angle: f32) -> Vec2 {
@@ -56,6 +71,15 @@ pub fn wrap_around(v: &Vec2) -> Vec2 {
 //?         [[[SIGNALS]]]
 pub struct Signals {
     pub spawn_molecule: bool,
+    pub spawn_charge: f32,
+    pub spawn_species: usize,
+    pub pause: bool,
+    pub step_frames: u32,
+    pub spawn_box: bool,
+    pub spawn_circle: bool,
+    pub spawn_rock: bool,
+    pub clear_obstacles: bool,
+    pub select_molecule: Option<u64>,
     pub new_sim: bool,
     pub new_sim_name: String,
 }
@@ -64,6 +88,15 @@ impl Signals {
     pub fn new() -> Self {
         Self {
             spawn_molecule: false,
+            spawn_charge: 0.0,
+            spawn_species: 0,
+            pause: false,
+            step_frames: 0,
+            spawn_box: false,
+            spawn_circle: false,
+            spawn_rock: false,
+            clear_obstacles: false,
+            select_molecule: None,
             new_sim: false,
             new_sim_name: String::new(),
         }
@@ -2,6 +2,7 @@ use crossbeam::channel::{Receiver, Sender};
 use nalgebra::{Unit, Complex, Isometry2};
 use rapier2d::{prelude::*, na::Vector2}; 
 use macroquad::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::f32::consts::PI;
 //use crate::element::*;
 use std::time::Duration;
@@ -9,6 +10,11 @@ use std::thread::sleep;
 use crossbeam::*;
 use macroquad::rand::*;
 use crate::consts::*;
+use crate::force_field::*;
+use crate::potential::*;
+use crate::particle::StaticShape;
+use crate::species::Species;
+use crate::kinetic::{Hit, CollisionsList, Collisions, ImpactStats, ObjectType};
 
 pub struct World {
     pub rigid_bodies: RigidBodySet,
@@ -22,10 +28,27 @@ pub struct World {
     impulse_joint_set: ImpulseJointSet,
     multibody_joint_set: MultibodyJointSet,
     ccd_solver: CCDSolver,
+    query_pipeline: QueryPipeline,
     physics_hooks: (),
     event_handler: ChannelEventCollector,
     //collision_send: Sender<CollisionEvent>,
     collision_recv: Receiver<CollisionEvent>,
+    contact_force_recv: Receiver<ContactForceEvent>,
+    bonds: HashMap<ImpulseJointHandle, Bond>,
+    boundary: BoundaryMode,
+    owners: HashMap<ColliderHandle, (u64, ObjectType)>,
+    collisions: CollisionsList,
+    touches: CollisionsList,
+    field_contacts: CollisionsList,
+    /// Number of collider pairs through which the field of the first object overlaps
+    /// the second; a field contact lasts until all of them stopped.
+    field_overlaps: HashMap<(u64, u64), u32>,
+    impacts: ImpactStats,
+    field_started: Vec<(u64, u64)>,
+    broken_bonds: Vec<(u64, u64)>,
+    broken_bonds_energy: f32,
+    pre_step_velocities: HashMap<RigidBodyHandle, Vector2<f32>>,
+    contact_losses: f32,
 }
 
 impl World {
@@ -40,7 +63,7 @@ impl World {
             rigid_bodies: RigidBodySet::new(),
             colliders: ColliderSet::new(),
             gravity: Vector2::new(0.0, 0.0),
-            integration_parameters: IntegrationParameters::default(),
+            integration_parameters: IntegrationParameters { dt: FIX_DT, ..Default::default() },
             physics_pipeline: PhysicsPipeline::new(),
             island_manager: IslandManager::new(),
             broad_phase: BroadPhase::new(),
@@ -48,55 +71,62 @@ impl World {
             impulse_joint_set: ImpulseJointSet::new(),
             multibody_joint_set: MultibodyJointSet::new(),
             ccd_solver: CCDSolver::new(),
+            query_pipeline: QueryPipeline::new(),
             physics_hooks: (),
             //collision_send: collision_send,
             event_handler: event_handler,
             //event_handler: ChannelEventCollector::new(collision_send2, contact_force_send2),
             collision_recv: collision_recv,
+            contact_force_recv: contact_force_recv,
+            bonds: HashMap::new(),
+            boundary: BoundaryMode::Open,
+            owners: HashMap::new(),
+            collisions: CollisionsList::new(),
+            touches: CollisionsList::new(),
+            field_contacts: CollisionsList::new(),
+            field_overlaps: HashMap::new(),
+            impacts: ImpactStats::new(),
+            field_started: vec![],
+            broken_bonds: vec![],
+            broken_bonds_energy: 0.0,
+            pre_step_velocities: HashMap::new(),
+            contact_losses: 0.0,
         }
     }
     
-    pub fn build(&mut self) {
-        println!("build");
+    pub fn build(&mut self, boundary: BoundaryMode) {
+        self.boundary = boundary;
+        if boundary != BoundaryMode::Walls {
+            return;
+        }
         let cx = WORLD_W/2.0;
         let cy = WORLD_H/2.0;
-        let edges1 = RigidBodyBuilder::fixed()
-            //.position(Isometry::new(Vector2::new(cx, cy), 0.0))
-            .build();
-        let edges2 = RigidBodyBuilder::fixed().position(Isometry::new(Vector2::new(cx, cy), 0.0))
-            .build();
-        let edges3 = RigidBodyBuilder::fixed().position(Isometry::new(Vector2::new(cx, cy), 0.0))
-            .build();
-        let edges4 = RigidBodyBuilder::fixed().position(Isometry::new(Vector2::new(cx, cy), 0.0))
-            .build();
-        let edge_left = ColliderBuilder::cuboid(100.0, WORLD_H)
-            .position(Isometry::new(Vector2::new(0.0, cy), 0.0))
+        let thickness = 100.0;
+        let edges = RigidBodyBuilder::fixed().build();
+        let edge_left = ColliderBuilder::cuboid(thickness, cy+2.0*thickness)
+            .position(Isometry::new(Vector2::new(-thickness, cy), 0.0))
             .active_collision_types(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_FIXED)
             .build();
-        let edge_right = ColliderBuilder::cuboid(100.0, WORLD_H)
-            .position(Isometry::new(Vector2::new(WORLD_W, cy), 0.0))
+        let edge_right = ColliderBuilder::cuboid(thickness, cy+2.0*thickness)
+            .position(Isometry::new(Vector2::new(WORLD_W+thickness, cy), 0.0))
             .active_collision_types(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_FIXED)
             .build();
-        let edge_top = ColliderBuilder::cuboid(WORLD_W, 100.0)
-            .position(Isometry::new(Vector2::new(cx, 0.0), 0.0))
+        let edge_top = ColliderBuilder::cuboid(cx+2.0*thickness, thickness)
+            .position(Isometry::new(Vector2::new(cx, -thickness), 0.0))
             .active_collision_types(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_FIXED)
             .build();
-        let edge_down = ColliderBuilder::cuboid(WORLD_W, 100.0)
-            .position(Isometry::new(Vector2::new(cx, WORLD_H), 0.0))
+        let edge_down = ColliderBuilder::cuboid(cx+2.0*thickness, thickness)
+            .position(Isometry::new(Vector2::new(cx, WORLD_H+thickness), 0.0))
             .active_collision_types(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_FIXED)
             .build();
-        let edge_handle1 = self.rigid_bodies.insert(edges1);
-        //let edge_handle2 = self.rigid_bodies.insert(edges2);
-        //let edge_handle3 = self.rigid_bodies.insert(edges3);
-        //let edge_handle4 = self.rigid_bodies.insert(edges4);
-        
-        _ = self.colliders.insert_with_parent(edge_left, edge_handle1, &mut self.rigid_bodies);
-        _ = self.colliders.insert_with_parent(edge_right, edge_handle1, &mut self.rigid_bodies);
-        _ = self.colliders.insert_with_parent(edge_top, edge_handle1, &mut self.rigid_bodies);
-        _ = self.colliders.insert_with_parent(edge_down, edge_handle1, &mut self.rigid_bodies);
-    }
-
-    pub fn add_circle_body(&mut self, position: &Vec2, radius: f32, field_radius: f32) -> RigidBodyHandle {
+        let edge_handle = self.rigid_bodies.insert(edges);
+        for (i, edge) in [edge_left, edge_right, edge_top, edge_down].into_iter().enumerate() {
+            let coll_handle = self.colliders.insert_with_parent(edge, edge_handle, &mut self.rigid_bodies);
+            self.owners.insert(coll_handle, (WALL_IDS[i], ObjectType::Obstacle));
+        }
+    }
+
+    pub fn add_circle_body(&mut self, id: u64, position: &Vec2, radius: f32, field_radius: f32, species: &Species) -> RigidBodyHandle {
         let iso = Isometry::new(Vector2::new(position.x, position.y), 0.0);
         //let iso = Isometry::new(Vector2::new(0.0, 0.0), 0.0);
         let ball = RigidBodyBuilder::dynamic()
@@ -105,65 +135,701 @@ impl World {
             .can_sleep(false).build();
         let collider = ColliderBuilder::ball(radius)
             .active_collision_types(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_DYNAMIC | ActiveCollisionTypes::DYNAMIC_FIXED)
-            //.active_events(ActiveEvents::COLLISION_EVENTS)
-            .restitution(0.9).friction(0.1)
+            .active_events(ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS)
+            .contact_force_event_threshold(CONTACT_FORCE_THRESHOLD)
+            .density(species.density).restitution(species.restitution).friction(species.friction)
             .restitution_combine_rule(CoefficientCombineRule::Max).friction_combine_rule(CoefficientCombineRule::Min)
             .build();
         let field = ColliderBuilder::ball(field_radius).active_collision_types(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_KINEMATIC)
-            .active_events(ActiveEvents::COLLISION_EVENTS).sensor(true)
+            .active_events(ActiveEvents::COLLISION_EVENTS).sensor(true).density(0.0)
             .build();
         //collider.set_active_events(ActiveEvents::COLLISION_EVENTS);
         let rb_handle = self.rigid_bodies.insert(ball);
         let coll_handle = self.colliders.insert_with_parent(collider, rb_handle, &mut self.rigid_bodies);
         let field_handle = self.colliders.insert_with_parent(field, rb_handle, &mut self.rigid_bodies);
+        self.owners.insert(coll_handle, (id, ObjectType::Agent));
+        self.owners.insert(field_handle, (id, ObjectType::Agent));
         return rb_handle;
     }
     
-    pub fn add_static_body(&mut self, position: &Vec2, width: f32, height: f32 ) -> RigidBodyHandle {
+    pub fn add_static_body(&mut self, id: u64, position: &Vec2, shape: &StaticShape) -> RigidBodyHandle {
         let iso = Isometry::new(Vector2::new(position.x, position.y), 0.0);
-        let cube = RigidBodyBuilder::dynamic().position(iso).can_sleep(false).build();
-        let mut collider = ColliderBuilder::cuboid(width, height)
+        let body = RigidBodyBuilder::fixed().position(iso).build();
+        let collider = static_collider(shape)
             .active_collision_types(ActiveCollisionTypes::default() | ActiveCollisionTypes::DYNAMIC_FIXED)
-            //.active_events(ActiveEvents::COLLISION_EVENTS)
             .restitution(1.0).friction(0.0).build();
-        //collider.set_active_events(ActiveEvents::COLLISION_EVENTS);
-        let rb_handle = self.rigid_bodies.insert(cube);
+        let rb_handle = self.rigid_bodies.insert(body);
+        let coll_handle = self.colliders.insert_with_parent(collider, rb_handle, &mut self.rigid_bodies);
+        self.owners.insert(coll_handle, (id, ObjectType::Obstacle));
+        return rb_handle;
+    }
+
+    /// Checks that a static shape placed at a position would not overlap any solid body.
+    pub fn is_area_free(&mut self, position: &Vec2, shape: &StaticShape) -> bool {
+        self.query_pipeline.update(&self.rigid_bodies, &self.colliders);
+        let iso = Isometry::new(Vector2::new(position.x, position.y), 0.0);
+        let collider = static_collider(shape).build();
+        let filter = QueryFilter::default().exclude_sensors();
+        return self.query_pipeline.intersection_with_shape(&self.rigid_bodies, &self.colliders, &iso, collider.shape(), filter).is_none();
+    }
+
+    pub fn add_source_body(&mut self, id: u64, position: &Vec2, radius: f32) -> RigidBodyHandle {
+        let body = RigidBodyBuilder::fixed().translation(Vector2::new(position.x, position.y)).build();
+        let collider = ColliderBuilder::ball(radius)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
+            .restitution(0.5).friction(0.0).build();
+        let rb_handle = self.rigid_bodies.insert(body);
         let coll_handle = self.colliders.insert_with_parent(collider, rb_handle, &mut self.rigid_bodies);
+        self.owners.insert(coll_handle, (id, ObjectType::Source));
         return rb_handle;
     }
 
-    fn reciv_events(&self) {
+    fn reciv_events(&mut self) {
+        self.collisions.clear();
+        self.field_started.clear();
         while let Ok(collision_event) = self.collision_recv.try_recv() {
-            if collision_event.sensor() {
-                match collision_event {
-                    CollisionEvent::Started(c1, c2, CollisionEventFlags::SENSOR) => {
-                        println!("COLLISION STARTED!");
+            match collision_event {
+                CollisionEvent::Started(c1, c2, flags) => {
+                    if flags.contains(CollisionEventFlags::SENSOR) {
+                        self.dispatch_field_contact(c1, c2);
+                    } else {
+                        self.dispatch_touch(c1, c2);
+                    }
+                },
+                CollisionEvent::Stopped(c1, c2, flags) => {
+                    let (Some((id1, _)), Some((id2, _))) = (self.owners.get(&c1).copied(), self.owners.get(&c2).copied()) else {
+                        continue;
+                    };
+                    if flags.contains(CollisionEventFlags::SENSOR) {
+                        if self.colliders.get(c1).is_some_and(|c| c.is_sensor()) {
+                            self.end_field_overlap(id1, id2);
+                        }
+                        if self.colliders.get(c2).is_some_and(|c| c.is_sensor()) {
+                            self.end_field_overlap(id2, id1);
+                        }
+                    } else {
+                        self.touches.remove_hit(id1, id2);
+                        self.touches.remove_hit(id2, id1);
+                    }
+                },
+            }
+        }
+    }
+
+    fn make_hits(&self, c1: ColliderHandle, c2: ColliderHandle) -> Option<((u64, Hit), (u64, Hit))> {
+        let (id1, type1) = *self.owners.get(&c1)?;
+        let (id2, type2) = *self.owners.get(&c2)?;
+        let co1 = self.colliders.get(c1)?;
+        let co2 = self.colliders.get(c2)?;
+        let p1 = Vec2::new(co1.translation().x, co1.translation().y);
+        let p2 = Vec2::new(co2.translation().x, co2.translation().y);
+        let mut normal = (p2 - p1).normalize_or_zero();
+        let mut overlap: f32 = 0.0;
+        if let Some(pair) = self.narrow_phase.contact_pair(c1, c2) {
+            if let Some((manifold, contact)) = pair.find_deepest_contact() {
+                let n = if pair.collider1 == c1 { manifold.data.normal } else { -manifold.data.normal };
+                normal = Vec2::new(n.x, n.y);
+                overlap = -contact.dist;
+            }
+        } else if let (Some(ball1), Some(ball2)) = (co1.shape().as_ball(), co2.shape().as_ball()) {
+            overlap = ball1.radius + ball2.radius - p1.distance(p2);
+        }
+        let hit1 = Hit { normal: normal, overlap: overlap, target_type: type2, target_id: id2 };
+        let hit2 = Hit { normal: -normal, overlap: overlap, target_type: type1, target_id: id1 };
+        return Some(((id1, hit1), (id2, hit2)));
+    }
+
+    fn reciv_contact_forces(&mut self) {
+        let dt = self.integration_parameters.dt;
+        while let Ok(force_event) = self.contact_force_recv.try_recv() {
+            let impulse = force_event.total_force_magnitude * dt;
+            for collider in [force_event.collider1, force_event.collider2] {
+                match self.owners.get(&collider) {
+                    Some((id, ObjectType::Agent)) => {
+                        self.impacts.add_impact(*id, impulse);
+                    },
+                    Some((id, ObjectType::Obstacle)) => {
+                        if let Some(wall) = WALL_IDS.iter().position(|wall_id| wall_id == id) {
+                            self.impacts.add_wall_impulse(wall, impulse);
+                        }
                     },
-                    CollisionEvent::Stopped(_, _, _) => {},
                     _ => {},
                 }
             }
         }
+        self.impacts.update(dt, [WORLD_H, WORLD_H, WORLD_W, WORLD_W]);
+    }
+
+    /// Kinetic energy of the relative motion lost in inelastic contacts during the last
+    /// step. Only pairs which were approaching each other before the step are counted.
+    fn measure_contact_losses(&mut self) {
+        self.contact_losses = 0.0;
+        for pair in self.narrow_phase.contact_pairs() {
+            if !pair.has_any_active_contact {
+                continue;
+            }
+            let Some(manifold) = pair.manifolds.first() else {
+                continue;
+            };
+            let body1 = self.colliders.get(pair.collider1).and_then(|c| c.parent());
+            let body2 = self.colliders.get(pair.collider2).and_then(|c| c.parent());
+            let (Some(h1), Some(h2)) = (body1, body2) else {
+                continue;
+            };
+            let (Some(rb1), Some(rb2)) = (self.rigid_bodies.get(h1), self.rigid_bodies.get(h2)) else {
+                continue;
+            };
+            let pre1 = self.pre_step_velocities.get(&h1).copied().unwrap_or(*rb1.linvel());
+            let pre2 = self.pre_step_velocities.get(&h2).copied().unwrap_or(*rb2.linvel());
+            let pre_rel = pre2 - pre1;
+            if pre_rel.dot(&manifold.data.normal) >= 0.0 {
+                continue;
+            }
+            let post_rel = rb2.linvel() - rb1.linvel();
+            let loss = 0.5 * reduced_mass(rb1, rb2) * (pre_rel.norm_squared() - post_rel.norm_squared());
+            self.contact_losses += loss.max(0.0);
+        }
+    }
+
+    /// Velocity of a body at the start of the last step; fixed bodies do not move.
+    pub fn get_pre_step_velocity(&self, handle: RigidBodyHandle) -> Vec2 {
+        let velocity = self.pre_step_velocities.get(&handle).copied().unwrap_or(Vector2::zeros());
+        return Vec2::new(velocity.x, velocity.y);
+    }
+
+    /// Kinetic energy dissipated by restitution and friction in the last step.
+    pub fn get_contact_losses(&self) -> f32 {
+        return self.contact_losses;
+    }
+
+    /// Kinetic energy of the relative motion of two colliding bodies before the step
+    /// in which they collided (collision events only arrive after the bounce).
+    fn get_impact_energy(&self, c1: ColliderHandle, c2: ColliderHandle) -> f32 {
+        let handle1 = self.colliders.get(c1).and_then(|c| c.parent());
+        let handle2 = self.colliders.get(c2).and_then(|c| c.parent());
+        let (Some(h1), Some(h2)) = (handle1, handle2) else {
+            return 0.0;
+        };
+        match (self.rigid_bodies.get(h1), self.rigid_bodies.get(h2)) {
+            (Some(rb1), Some(rb2)) => {
+                let v_rel = self.get_pre_step_velocity(h1) - self.get_pre_step_velocity(h2);
+                let v_rel = Vector2::new(v_rel.x, v_rel.y);
+                return 0.5 * reduced_mass(rb1, rb2) * v_rel.norm_squared();
+            },
+            _ => {
+                return 0.0;
+            },
+        }
+    }
+
+    fn dispatch_touch(&mut self, c1: ColliderHandle, c2: ColliderHandle) {
+        let energy = self.get_impact_energy(c1, c2);
+        self.impacts.add_collision(energy);
+        if let Some(((id1, hit1), (id2, hit2))) = self.make_hits(c1, c2) {
+            self.collisions.add_hit(id1, hit1);
+            self.collisions.add_hit(id2, hit2);
+            self.touches.add_hit(id1, hit1);
+            self.touches.add_hit(id2, hit2);
+        }
+    }
+
+    fn dispatch_field_contact(&mut self, c1: ColliderHandle, c2: ColliderHandle) {
+        if let Some(((id1, hit1), (id2, hit2))) = self.make_hits(c1, c2) {
+            if id1 == id2 {
+                return;
+            }
+            if hit1.target_type == ObjectType::Agent && hit2.target_type == ObjectType::Agent {
+                self.field_started.push((id1.min(id2), id1.max(id2)));
+            }
+            if self.colliders.get(c1).is_some_and(|c| c.is_sensor()) {
+                self.field_contacts.add_hit(id1, hit1);
+                *self.field_overlaps.entry((id1, id2)).or_insert(0) += 1;
+            }
+            if self.colliders.get(c2).is_some_and(|c| c.is_sensor()) {
+                self.field_contacts.add_hit(id2, hit2);
+                *self.field_overlaps.entry((id2, id1)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Drops the field contact of `id` with `target_id` once no collider pair overlaps.
+    fn end_field_overlap(&mut self, id: u64, target_id: u64) {
+        let Some(count) = self.field_overlaps.get_mut(&(id, target_id)) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.field_overlaps.remove(&(id, target_id));
+            self.field_contacts.remove_hit(id, target_id);
+        }
+    }
+
+    /// Solid hits which started during the last step.
+    pub fn get_collisions(&self, id: u64) -> Option<&Collisions> {
+        return self.collisions.get_collisions(id);
+    }
+
+    /// Solid contacts which are still touching.
+    pub fn get_touches(&self, id: u64) -> Option<&Collisions> {
+        return self.touches.get_collisions(id);
+    }
+
+    /// Objects overlapping the field (sensor) of a given object.
+    pub fn get_field_contacts(&self, id: u64) -> Option<&Collisions> {
+        return self.field_contacts.get_collisions(id);
+    }
+
+    /// Pairs of agents whose fields started to overlap during the last step.
+    pub fn get_new_field_contacts(&self) -> &[(u64, u64)] {
+        return &self.field_started;
+    }
+
+    /// Bonds broken during the last step, given as owner ids of both bodies.
+    pub fn get_broken_bonds(&self) -> &[(u64, u64)] {
+        return &self.broken_bonds;
+    }
+
+    /// Spring energy released by the bonds broken during the last step.
+    pub fn get_broken_bonds_energy(&self) -> f32 {
+        return self.broken_bonds_energy;
+    }
+
+    pub fn get_impact_stats(&self) -> &ImpactStats {
+        return &self.impacts;
+    }
+
+    pub fn get_owner(&self, collider: ColliderHandle) -> Option<(u64, ObjectType)> {
+        return self.owners.get(&collider).copied();
+    }
+
+    pub fn create_bond(&mut self, body1: RigidBodyHandle, body2: RigidBodyHandle) -> Option<ImpulseJointHandle> {
+        if body1 == body2 || self.impulse_joint_set.joints_between(body1, body2).next().is_some() {
+            return None;
+        }
+        let (Some(rb1), Some(rb2)) = (self.rigid_bodies.get(body1), self.rigid_bodies.get(body2)) else {
+            return None;
+        };
+        if !rb1.is_dynamic() || !rb2.is_dynamic() {
+            return None;
+        }
+        let id1 = self.get_body_owner(body1)?;
+        let id2 = self.get_body_owner(body2)?;
+        let (radius1, field1) = self.get_body_radii(body1);
+        let (radius2, field2) = self.get_body_radii(body2);
+        let rest_length = radius1 + radius2;
+        // Fields of both bodies overlap when a bond forms, so it can start longer than one field range.
+        let distance = self.minimum_image(rb1.translation() - rb2.translation()).norm();
+        let break_length = distance.max(field1.max(field2) + rest_length) * BOND_BREAK_RATIO;
+        let joint = RopeJointBuilder::new()
+            .motor_position(rest_length, BOND_STIFFNESS, BOND_DAMPING)
+            .build();
+        let joint_handle = self.impulse_joint_set.insert(body1, body2, joint, true);
+        self.bonds.insert(joint_handle, Bond { body1, body2, id1, id2, rest_length, break_length });
+        return Some(joint_handle);
+    }
+
+    /// Energy stored in the spring of a stretched bond.
+    pub fn get_bond_energy(&self, joint_handle: ImpulseJointHandle) -> f32 {
+        let Some(bond) = self.bonds.get(&joint_handle) else {
+            return 0.0;
+        };
+        match (self.rigid_bodies.get(bond.body1), self.rigid_bodies.get(bond.body2)) {
+            (Some(rb1), Some(rb2)) => {
+                let stretch = (self.minimum_image(rb1.translation() - rb2.translation()).norm() - bond.rest_length).max(0.0);
+                return 0.5 * BOND_STIFFNESS * stretch * stretch;
+            },
+            _ => {
+                return 0.0;
+            },
+        }
+    }
+
+    fn get_body_owner(&self, handle: RigidBodyHandle) -> Option<u64> {
+        let rb = self.rigid_bodies.get(handle)?;
+        let collider = rb.colliders().first()?;
+        return self.owners.get(collider).map(|(id, _)| *id);
+    }
+
+    fn break_stretched_bonds(&mut self) {
+        self.broken_bonds.clear();
+        self.broken_bonds_energy = 0.0;
+        let mut broken: Vec<ImpulseJointHandle> = vec![];
+        for (joint_handle, bond) in self.bonds.iter() {
+            match (self.rigid_bodies.get(bond.body1), self.rigid_bodies.get(bond.body2)) {
+                (Some(rb1), Some(rb2)) => {
+                    let distance = self.minimum_image(rb1.translation() - rb2.translation()).norm();
+                    if distance > bond.break_length {
+                        broken.push(*joint_handle);
+                    }
+                },
+                _ => {
+                    broken.push(*joint_handle);
+                },
+            }
+        }
+        for joint_handle in broken {
+            self.broken_bonds_energy += self.get_bond_energy(joint_handle);
+            if let Some(bond) = self.bonds.remove(&joint_handle) {
+                self.broken_bonds.push((bond.id1, bond.id2));
+            }
+            _ = self.impulse_joint_set.remove(joint_handle, true);
+        }
+    }
+
+    fn get_body_radii(&self, handle: RigidBodyHandle) -> (f32, f32) {
+        let mut radius: f32 = 0.0;
+        let mut field_radius: f32 = 0.0;
+        if let Some(rb) = self.rigid_bodies.get(handle) {
+            for c in rb.colliders().iter() {
+                if let Some(collider) = self.colliders.get(*c) {
+                    if let Some(ball) = collider.shape().as_ball() {
+                        if collider.is_sensor() {
+                            field_radius = ball.radius;
+                        } else {
+                            radius = ball.radius;
+                        }
+                    }
+                }
+            }
+        }
+        return (radius, field_radius);
+    }
+
+    /// Bond segments from the first body towards the nearest image of the second.
+    pub fn get_bond_lines(&self) -> Vec<(Vec2, Vec2)> {
+        let mut lines: Vec<(Vec2, Vec2)> = vec![];
+        for (_, bond) in self.bonds.iter() {
+            if let (Some(rb1), Some(rb2)) = (self.rigid_bodies.get(bond.body1), self.rigid_bodies.get(bond.body2)) {
+                let offset = self.minimum_image(rb2.translation() - rb1.translation());
+                let p1 = Vec2::new(rb1.translation().x, rb1.translation().y);
+                lines.push((p1, p1 + Vec2::new(offset.x, offset.y)));
+            }
+        }
+        return lines;
+    }
+
+    pub fn get_bonds_num(&self) -> usize {
+        return self.bonds.len();
     }
 
+    /// Removes a body with its colliders and joints, and forgets its bonds and contacts.
     pub fn remove_physics_object(&mut self, body_handle: RigidBodyHandle) {
+        self.bonds.retain(|_, bond| bond.body1 != body_handle && bond.body2 != body_handle);
+        if let Some(rb) = self.rigid_bodies.get(body_handle) {
+            for c in rb.colliders().iter() {
+                if let Some((id, _)) = self.owners.remove(c) {
+                    self.impacts.remove(id);
+                    self.collisions.remove_object(id);
+                    self.touches.remove_object(id);
+                    self.field_contacts.remove_object(id);
+                    self.field_overlaps.retain(|(id1, id2), _| *id1 != id && *id2 != id);
+                }
+            }
+        }
         _ = self.rigid_bodies.remove(body_handle, &mut self.island_manager, &mut self.colliders, &mut self.impulse_joint_set, &mut self.multibody_joint_set, true);
     }
 
+    pub fn get_kinetic_energy(&self) -> f32 {
+        let mut kinetic_energy: f32 = 0.0;
+        for (_, rb) in self.rigid_bodies.iter() {
+            if rb.is_dynamic() {
+                kinetic_energy += 0.5 * rb.mass() * rb.linvel().norm_squared();
+            }
+        }
+        return kinetic_energy;
+    }
+
+    /// Energy stored in pair interactions, electrostatics and stretched bonds.
+    pub fn get_potential_energy(&self, potential: &Potential, charges: &HashMap<RigidBodyHandle, f32>, k: f32) -> f32 {
+        let mut energy: f32 = 0.0;
+        for (rbh1, rbh2, offset) in self.get_neighbour_pairs() {
+            energy += pair_energy(potential, charges, k, rbh1, rbh2, offset.norm());
+        }
+        for joint_handle in self.bonds.keys() {
+            energy += self.get_bond_energy(*joint_handle);
+        }
+        return energy;
+    }
+
+    /// Part of the potential energy which involves a given body: its pairs with
+    /// neighbours and its bonds.
+    pub fn get_body_potential_energy(&self, handle: RigidBodyHandle, potential: &Potential, charges: &HashMap<RigidBodyHandle, f32>, k: f32) -> f32 {
+        let mut energy: f32 = 0.0;
+        for (rbh1, rbh2, offset) in self.get_neighbour_pairs() {
+            if rbh1 == handle || rbh2 == handle {
+                energy += pair_energy(potential, charges, k, rbh1, rbh2, offset.norm());
+            }
+        }
+        for (joint_handle, bond) in self.bonds.iter() {
+            if bond.body1 == handle || bond.body2 == handle {
+                energy += self.get_bond_energy(*joint_handle);
+            }
+        }
+        return energy;
+    }
+
+    /// Brings spatial queries up to date with bodies added, removed or moved since
+    /// the last physics step.
+    pub fn update_query_pipeline(&mut self) {
+        self.query_pipeline.update(&self.rigid_bodies, &self.colliders);
+    }
+
+    /// Casts rays from a body in given absolute directions. Returns the distance and
+    /// owner type of the first solid collider hit by every ray, if any within range.
+    pub fn cast_rays(&self, handle: RigidBodyHandle, directions: &[Vec2], range: f32) -> Vec<Option<(f32, ObjectType)>> {
+        let Some(rb) = self.rigid_bodies.get(handle) else {
+            return vec![None; directions.len()];
+        };
+        let origin = point![rb.translation().x, rb.translation().y];
+        let filter = QueryFilter::new().exclude_sensors().exclude_rigid_body(handle);
+        let mut hits: Vec<Option<(f32, ObjectType)>> = vec![];
+        for dir in directions.iter() {
+            let ray = Ray::new(origin, vector![dir.x, dir.y]);
+            let hit = self.query_pipeline.cast_ray(&self.rigid_bodies, &self.colliders, &ray, range, true, filter)
+                .and_then(|(collider, toi)| self.owners.get(&collider).map(|(_, object_type)| (toi, *object_type)));
+            hits.push(hit);
+        }
+        return hits;
+    }
+
+    /// Solid objects whose colliders intersect a circle of given range around a body,
+    /// with their distance and position. In periodic mode objects across an edge are
+    /// found too and their position is given as seen from the body.
+    pub fn find_neighbours(&self, handle: RigidBodyHandle, range: f32) -> Vec<(u64, ObjectType, f32, Vec2)> {
+        let Some(rb) = self.rigid_bodies.get(handle) else {
+            return vec![];
+        };
+        let pos = *rb.translation();
+        let area = Ball::new(range);
+        let filter = QueryFilter::new().exclude_sensors().exclude_rigid_body(handle);
+        let mut neighbours: Vec<(u64, ObjectType, f32, Vec2)> = vec![];
+        for shift in self.get_periodic_images(&pos, range) {
+            let image = pos + shift;
+            self.query_pipeline.intersections_with_shape(&self.rigid_bodies, &self.colliders, &Isometry::translation(image.x, image.y), &area, filter, |collider| {
+                if let (Some(co), Some((id, object_type))) = (self.colliders.get(collider), self.owners.get(&collider)) {
+                    let point = co.shape().project_point(co.position(), &point![image.x, image.y], true).point;
+                    let target = Vec2::new(point.x - shift.x, point.y - shift.y);
+                    let distance = target.distance(Vec2::new(pos.x, pos.y));
+                    neighbours.push((*id, *object_type, distance, target));
+                }
+                return true;
+            });
+        }
+        return neighbours;
+    }
+
+    /// Kinetic temperature of the dynamic bodies (2D, Boltzmann constant equal to 1).
+    pub fn get_kinetic_temperature(&self) -> f32 {
+        let mut kinetic_energy: f32 = 0.0;
+        let mut bodies_num: usize = 0;
+        for (_, rb) in self.rigid_bodies.iter() {
+            if rb.is_dynamic() {
+                kinetic_energy += 0.5 * rb.mass() * rb.linvel().norm_squared();
+                bodies_num += 1;
+            }
+        }
+        if bodies_num == 0 {
+            return 0.0;
+        }
+        return kinetic_energy / bodies_num as f32;
+    }
+
     pub fn get_physics_obj_num(&self) -> usize {
         let body_num = self.rigid_bodies.len();
         return body_num;
     }
 
-    pub fn step_physics(&mut self) {
-        for (rbh, rb) in self.rigid_bodies.iter_mut() {
-            let mut pos = Vec2::new((rb.position().translation.x-WORLD_W/2.0).to_owned(), (rb.position().translation.y-WORLD_H/2.0).to_owned());
-            let r2 = pos.distance(Vec2::new(WORLD_W/2.0, WORLD_H/2.0));
-            let gvec2 = pos.normalize();
-            let f = GRAV * (gvec2/r2);
+    pub fn reset_forces(&mut self) {
+        for (_, rb) in self.rigid_bodies.iter_mut() {
             rb.reset_forces(true);
-            rb.add_force(-Vector2::new(f.x, f.y), true);
+            rb.reset_torques(true);
+        }
+    }
+
+    /// Applies force fields and returns the work they will do over the next step.
+    pub fn apply_force_fields(&mut self, fields: &[ForceField]) -> f32 {
+        let dt = self.integration_parameters.dt;
+        let mut work: f32 = 0.0;
+        for (_, rb) in self.rigid_bodies.iter_mut() {
+            if !rb.is_dynamic() {
+                continue;
+            }
+            let pos = Vec2::new(rb.translation().x, rb.translation().y);
+            let mass = rb.mass();
+            let mut f = Vec2::ZERO;
+            for field in fields.iter() {
+                f += field.force_at(pos, mass);
+            }
+            let force = Vector2::new(f.x, f.y);
+            work += force.dot(rb.linvel()) * dt;
+            rb.add_force(force, true);
+        }
+        return work;
+    }
+
+    fn get_neighbour_pairs(&self) -> Vec<(RigidBodyHandle, RigidBodyHandle, Vector2<f32>)> {
+        let mut pairs: HashSet<(RigidBodyHandle, RigidBodyHandle)> = HashSet::new();
+        let mut neighbour_pairs: Vec<(RigidBodyHandle, RigidBodyHandle, Vector2<f32>)> = vec![];
+        for (rbh, rb) in self.rigid_bodies.iter() {
+            if !rb.is_dynamic() {
+                continue;
+            }
+            let (_, field_radius) = self.get_body_radii(rbh);
+            if field_radius <= 0.0 {
+                continue;
+            }
+            let pos1 = *rb.translation();
+            let field = Ball::new(field_radius);
+            let filter = QueryFilter::only_dynamic().exclude_sensors().exclude_rigid_body(rbh);
+            let mut neighbours: Vec<(RigidBodyHandle, Vector2<f32>)> = vec![];
+            for shift in self.get_periodic_images(&pos1, field_radius) {
+                let image = Isometry::new(pos1 + shift, rb.rotation().angle());
+                self.query_pipeline.intersections_with_shape(&self.rigid_bodies, &self.colliders, &image, &field, filter, |collider| {
+                    if let Some(parent) = self.colliders.get(collider).and_then(|c| c.parent()) {
+                        neighbours.push((parent, shift));
+                    }
+                    return true;
+                });
+            }
+            for (other, shift) in neighbours {
+                let pair = if rbh.0 < other.0 { (rbh, other) } else { (other, rbh) };
+                if !pairs.insert(pair) {
+                    continue;
+                }
+                let pos2 = *self.rigid_bodies[other].translation();
+                neighbour_pairs.push((rbh, other, pos1 + shift - pos2));
+            }
+        }
+        return neighbour_pairs;
+    }
+
+    /// Translations under which a body's field should be queried. In periodic mode
+    /// fields reaching over an edge are also checked on the opposite side.
+    fn get_periodic_images(&self, pos: &Vector2<f32>, range: f32) -> Vec<Vector2<f32>> {
+        let mut shifts_x: Vec<f32> = vec![0.0];
+        let mut shifts_y: Vec<f32> = vec![0.0];
+        if self.boundary == BoundaryMode::Periodic {
+            if pos.x < range {
+                shifts_x.push(WORLD_W);
+            } else if pos.x > WORLD_W - range {
+                shifts_x.push(-WORLD_W);
+            }
+            if pos.y < range {
+                shifts_y.push(WORLD_H);
+            } else if pos.y > WORLD_H - range {
+                shifts_y.push(-WORLD_H);
+            }
+        }
+        let mut shifts: Vec<Vector2<f32>> = vec![];
+        for sx in shifts_x.iter() {
+            for sy in shifts_y.iter() {
+                shifts.push(Vector2::new(*sx, *sy));
+            }
+        }
+        return shifts;
+    }
+
+    /// Moves bodies which left the world by exactly one world size, matching the
+    /// shifts used by `get_periodic_images`. Bonded bodies are moved together once
+    /// the centre of their group leaves the world, as a bond joint cannot span the
+    /// edge; members may stick out of the world until then.
+    fn wrap_bodies(&mut self) {
+        for group in self.get_bonded_groups() {
+            let centre = group.iter().filter_map(|h| self.rigid_bodies.get(*h)).map(|rb| *rb.translation()).sum::<Vector2<f32>>() / group.len() as f32;
+            let shift = Vector2::new(centre.x.rem_euclid(WORLD_W), centre.y.rem_euclid(WORLD_H)) - centre;
+            if shift == Vector2::zeros() {
+                continue;
+            }
+            for handle in group {
+                if let Some(rb) = self.rigid_bodies.get_mut(handle) {
+                    let pos = *rb.translation();
+                    rb.set_translation(pos + shift, true);
+                }
+            }
+        }
+    }
+
+    /// Dynamic bodies split into groups connected by bonds.
+    fn get_bonded_groups(&self) -> Vec<Vec<RigidBodyHandle>> {
+        let mut links: HashMap<RigidBodyHandle, Vec<RigidBodyHandle>> = HashMap::new();
+        for bond in self.bonds.values() {
+            links.entry(bond.body1).or_default().push(bond.body2);
+            links.entry(bond.body2).or_default().push(bond.body1);
+        }
+        let mut visited: HashSet<RigidBodyHandle> = HashSet::new();
+        let mut groups: Vec<Vec<RigidBodyHandle>> = vec![];
+        for (handle, rb) in self.rigid_bodies.iter() {
+            if !rb.is_dynamic() || !visited.insert(handle) {
+                continue;
+            }
+            let mut group: Vec<RigidBodyHandle> = vec![];
+            let mut stack: Vec<RigidBodyHandle> = vec![handle];
+            while let Some(current) = stack.pop() {
+                group.push(current);
+                for next in links.get(&current).into_iter().flatten() {
+                    if visited.insert(*next) {
+                        stack.push(*next);
+                    }
+                }
+            }
+            groups.push(group);
+        }
+        return groups;
+    }
+
+    /// Shortest offset between two points; in periodic mode across the nearest edge.
+    fn minimum_image(&self, offset: Vector2<f32>) -> Vector2<f32> {
+        if self.boundary != BoundaryMode::Periodic {
+            return offset;
         }
+        let x = offset.x - WORLD_W * (offset.x / WORLD_W).round();
+        let y = offset.y - WORLD_H * (offset.y / WORLD_H).round();
+        return Vector2::new(x, y);
+    }
+
+    fn add_pair_forces(&mut self, forces: HashMap<RigidBodyHandle, Vector2<f32>>) {
+        for (rbh, f) in forces {
+            if let Some(rb) = self.rigid_bodies.get_mut(rbh) {
+                rb.add_force(f, true);
+            }
+        }
+    }
+
+    pub fn apply_interactions(&mut self, potential: &Potential) {
+        if *potential == Potential::None {
+            return;
+        }
+        let mut forces: HashMap<RigidBodyHandle, Vector2<f32>> = HashMap::new();
+        for (rbh1, rbh2, offset) in self.get_neighbour_pairs() {
+            let r = offset.norm();
+            if r <= f32::EPSILON {
+                continue;
+            }
+            let f = offset / r * potential.force(r);
+            *forces.entry(rbh1).or_insert(Vector2::zeros()) += f;
+            *forces.entry(rbh2).or_insert(Vector2::zeros()) -= f;
+        }
+        self.add_pair_forces(forces);
+    }
+
+    pub fn apply_electrostatics(&mut self, charges: &HashMap<RigidBodyHandle, f32>, k: f32) {
+        let mut forces: HashMap<RigidBodyHandle, Vector2<f32>> = HashMap::new();
+        for (rbh1, rbh2, offset) in self.get_neighbour_pairs() {
+            let q1 = charges.get(&rbh1).copied().unwrap_or(0.0);
+            let q2 = charges.get(&rbh2).copied().unwrap_or(0.0);
+            let r = offset.norm();
+            if q1 == 0.0 || q2 == 0.0 || r <= f32::EPSILON {
+                continue;
+            }
+            let f = offset / r * (k * q1 * q2 / r.max(1.0).powi(2));
+            *forces.entry(rbh1).or_insert(Vector2::zeros()) += f;
+            *forces.entry(rbh2).or_insert(Vector2::zeros()) -= f;
+        }
+        self.add_pair_forces(forces);
+    }
+
+    pub fn step_physics(&mut self) {
+        self.pre_step_velocities = self.rigid_bodies.iter().filter(|(_, rb)| rb.is_dynamic()).map(|(h, rb)| (h, *rb.linvel())).collect();
         self.physics_pipeline.step(
             &self.gravity,
             &self.integration_parameters,
@@ -175,11 +841,17 @@ impl World {
             &mut self.impulse_joint_set,
             &mut self.multibody_joint_set,
             &mut self.ccd_solver,
-            None,
+            Some(&mut self.query_pipeline),
             &self.physics_hooks,
             &self.event_handler,
         );
         self.reciv_events();
+        self.reciv_contact_forces();
+        self.measure_contact_losses();
+        self.break_stretched_bonds();
+        if self.boundary == BoundaryMode::Periodic {
+            self.wrap_bodies();
+        }
     }
 
     fn iso_to_vec2_rot(&self, isometry: &Isometry<Real>) -> (Vec2, f32) {
@@ -204,14 +876,201 @@ impl World {
                 }
             }
         }
-        let data = PhysicsData {position: pos, rotation: rot, field_radius: field_rad};
+        let linvel = rb.linvel();
+        let data = PhysicsData {
+            position: pos, rotation: rot, field_radius: field_rad, velocity: Vec2::new(linvel.x, linvel.y), mass: rb.mass(),
+            angular_velocity: rb.angvel(), inertia: rb.mass_properties().local_mprops.principal_inertia(),
+        };
         return data;
     }
+
+    pub fn add_force_and_torque(&mut self, handle: RigidBodyHandle, force: Vec2, torque: f32) {
+        if let Some(rb) = self.rigid_bodies.get_mut(handle) {
+            rb.add_force(Vector2::new(force.x, force.y), true);
+            rb.add_torque(torque, true);
+        }
+    }
+
+    pub fn set_velocity(&mut self, handle: RigidBodyHandle, velocity: Vec2) {
+        if let Some(rb) = self.rigid_bodies.get_mut(handle) {
+            rb.set_linvel(Vector2::new(velocity.x, velocity.y), true);
+        }
+    }
+}
+
+
+fn static_collider(shape: &StaticShape) -> ColliderBuilder {
+    match shape {
+        StaticShape::Box { width, height } => ColliderBuilder::cuboid(width/2.0, height/2.0),
+        StaticShape::Circle { radius } => ColliderBuilder::ball(*radius),
+        StaticShape::Polygon { points } => {
+            let hull: Vec<Point<f32>> = points.iter().map(|p| point![p.x, p.y]).collect();
+            ColliderBuilder::convex_hull(&hull).unwrap_or(ColliderBuilder::ball(1.0))
+        },
+    }
+}
+
+/// Interaction and electrostatic energy of a pair of bodies at distance r.
+fn pair_energy(potential: &Potential, charges: &HashMap<RigidBodyHandle, f32>, k: f32, rbh1: RigidBodyHandle, rbh2: RigidBodyHandle, r: f32) -> f32 {
+    if r <= f32::EPSILON {
+        return 0.0;
+    }
+    let q1 = charges.get(&rbh1).copied().unwrap_or(0.0);
+    let q2 = charges.get(&rbh2).copied().unwrap_or(0.0);
+    return potential.energy(r) + k * q1 * q2 / r.max(1.0);
+}
+
+/// Reduced mass of a pair of bodies; a fixed body acts as an infinite mass.
+fn reduced_mass(rb1: &RigidBody, rb2: &RigidBody) -> f32 {
+    let (m1, m2) = (rb1.mass(), rb2.mass());
+    if !rb1.is_dynamic() {
+        return m2;
+    } else if !rb2.is_dynamic() {
+        return m1;
+    }
+    return m1*m2/(m1+m2);
+}
+
+/// Owner ids of the left, right, top and bottom wall colliders.
+pub const WALL_IDS: [u64; 4] = [u64::MAX, u64::MAX-1, u64::MAX-2, u64::MAX-3];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BoundaryMode {
+    Walls,
+    Periodic,
+    Open,
+}
+
+impl BoundaryMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BoundaryMode::Walls => "Walls",
+            BoundaryMode::Periodic => "Periodic",
+            BoundaryMode::Open => "Open",
+        }
+    }
 }
 
+pub struct Bond {
+    pub body1: RigidBodyHandle,
+    pub body2: RigidBodyHandle,
+    pub id1: u64,
+    pub id2: u64,
+    pub rest_length: f32,
+    pub break_length: f32,
+}
 
 pub struct PhysicsData {
     pub position: Vec2,
     pub rotation: f32,
     pub field_radius: f32,
-}
\ No newline at end of file
+    pub velocity: Vec2,
+    pub mass: f32,
+    pub angular_velocity: f32,
+    pub inertia: f32,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::species::SpeciesRegistry;
+
+    #[test]
+    fn bonded_pair_crosses_periodic_edge_together() {
+        let registry = SpeciesRegistry::parse("Oxygen, O, 4.0, 1.2, #FF0D0D, 40, 0.9, 0.1, 2").unwrap();
+        let species = registry.get(0).unwrap();
+        let mut world = World::new();
+        world.build(BoundaryMode::Periodic);
+        let h1 = world.add_circle_body(1, &Vec2::new(WORLD_W - 20.0, 400.0), 4.0, 40.0, species);
+        let h2 = world.add_circle_body(2, &Vec2::new(WORLD_W - 28.0, 400.0), 4.0, 40.0, species);
+        assert!(world.create_bond(h1, h2).is_some());
+        world.set_velocity(h1, Vec2::new(60.0, 0.0));
+        world.set_velocity(h2, Vec2::new(60.0, 0.0));
+        for _ in 0..60 {
+            world.step_physics();
+            let (p1, p2) = (world.get_physics_data(h1).position, world.get_physics_data(h2).position);
+            assert!(p1.distance(p2) < 12.0, "bond spans the world: {} {}", p1, p2);
+        }
+        assert_eq!(world.get_bonds_num(), 1);
+        let (p1, p2) = (world.get_physics_data(h1).position, world.get_physics_data(h2).position);
+        assert!(p1.x < 200.0 && p2.x < 200.0, "pair did not wrap: {} {}", p1.x, p2.x);
+        for (a, b) in world.get_bond_lines() {
+            assert!(a.distance(b) < 12.0);
+        }
+    }
+
+    #[test]
+    fn bond_formed_at_field_overlap_survives() {
+        let registry = SpeciesRegistry::parse("Hydrogen, H, 2.0, 1.0, #FFFFFF, 24, 0.9, 0.1, 1").unwrap();
+        let species = registry.get(0).unwrap();
+        let mut world = World::new();
+        world.build(BoundaryMode::Walls);
+        let h1 = world.add_circle_body(1, &Vec2::new(400.0, 400.0), 2.0, 24.0, species);
+        let h2 = world.add_circle_body(2, &Vec2::new(447.0, 400.0), 2.0, 24.0, species);
+        assert!(world.create_bond(h1, h2).is_some());
+        world.set_velocity(h1, Vec2::ZERO);
+        world.set_velocity(h2, Vec2::ZERO);
+        for _ in 0..30 {
+            world.step_physics();
+            assert!(world.get_broken_bonds().is_empty());
+        }
+        assert_eq!(world.get_bonds_num(), 1);
+    }
+
+    #[test]
+    fn field_contact_lasts_while_any_overlap_remains() {
+        let registry = SpeciesRegistry::parse("Hydrogen, H, 2.0, 1.0, #FFFFFF, 24, 0.9, 0.1, 1").unwrap();
+        let species = registry.get(0).unwrap();
+        let mut world = World::new();
+        world.build(BoundaryMode::Walls);
+        let h1 = world.add_circle_body(1, &Vec2::new(400.0, 400.0), 2.0, 24.0, species);
+        let h2 = world.add_circle_body(2, &Vec2::new(420.0, 400.0), 2.0, 24.0, species);
+        let sees = |world: &World, id: u64, target_id: u64| world.get_field_contacts(id).is_some_and(|c| c.iter().any(|hit| hit.target_id == target_id));
+        world.set_velocity(h1, Vec2::ZERO);
+        world.set_velocity(h2, Vec2::ZERO);
+        world.step_physics();
+        assert!(sees(&world, 1, 2) && sees(&world, 2, 1));
+        // The field of 1 no longer reaches the body of 2, but both fields still overlap.
+        world.rigid_bodies[h2].set_translation(Vector2::new(440.0, 400.0), true);
+        world.step_physics();
+        assert!(sees(&world, 1, 2) && sees(&world, 2, 1));
+        world.rigid_bodies[h2].set_translation(Vector2::new(460.0, 400.0), true);
+        world.step_physics();
+        assert!(!sees(&world, 1, 2) && !sees(&world, 2, 1));
+    }
+
+    #[test]
+    fn body_potential_is_what_its_removal_takes_away() {
+        let registry = SpeciesRegistry::parse("Oxygen, O, 4.0, 1.2, #FF0D0D, 40, 0.9, 0.1, 2").unwrap();
+        let species = registry.get(0).unwrap();
+        let mut world = World::new();
+        world.build(BoundaryMode::Walls);
+        let h1 = world.add_circle_body(1, &Vec2::new(400.0, 400.0), 4.0, 40.0, species);
+        let h2 = world.add_circle_body(2, &Vec2::new(415.0, 400.0), 4.0, 40.0, species);
+        let h3 = world.add_circle_body(3, &Vec2::new(405.0, 420.0), 4.0, 40.0, species);
+        assert!(world.create_bond(h2, h3).is_some());
+        let potential = Potential::default_lennard_jones();
+        let charges: HashMap<RigidBodyHandle, f32> = [(h1, 1.0), (h2, -2.0), (h3, 1.0)].into_iter().collect();
+        world.update_query_pipeline();
+        let total = world.get_potential_energy(&potential, &charges, 500.0);
+        let body = world.get_body_potential_energy(h2, &potential, &charges, 500.0);
+        world.remove_physics_object(h2);
+        world.update_query_pipeline();
+        let rest = world.get_potential_energy(&potential, &charges, 500.0);
+        assert!(body.abs() > 1.0);
+        assert!((total - body - rest).abs() < 1e-2 * total.abs().max(1.0));
+    }
+
+    #[test]
+    fn bond_lines_use_minimum_image() {
+        let registry = SpeciesRegistry::parse("Oxygen, O, 4.0, 1.2, #FF0D0D, 40, 0.9, 0.1, 2").unwrap();
+        let species = registry.get(0).unwrap();
+        let mut world = World::new();
+        world.build(BoundaryMode::Periodic);
+        let h1 = world.add_circle_body(1, &Vec2::new(2.0, 400.0), 4.0, 40.0, species);
+        let h2 = world.add_circle_body(2, &Vec2::new(WORLD_W - 6.0, 400.0), 4.0, 40.0, species);
+        assert!(world.create_bond(h1, h2).is_some());
+        let lines = world.get_bond_lines();
+        assert_eq!(lines.len(), 1);
+        assert!((lines[0].0.distance(lines[0].1) - 8.0).abs() < 1e-3);
+    }
+}